@@ -0,0 +1,117 @@
+//! Compare the SIMD and scalar bitmap downsampling paths.
+//!
+//! Run with `cargo bench --bench downsample`.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use carbonyl::{
+    gfx::{Color, Rect, Size},
+    output::{Painter, Renderer, average_rows, average_rows_scalar},
+};
+
+/// Time spent measuring each case
+const DURATION: Duration = Duration::from_secs(2);
+
+/// Average time of a call to `run`, repeated for about `DURATION`
+fn measure(name: &str, mut run: impl FnMut()) -> Duration {
+    // Warm up caches and lazily initialized tables
+    run();
+
+    let start = Instant::now();
+    let mut iterations = 0;
+
+    while start.elapsed() < DURATION {
+        run();
+        iterations += 1;
+    }
+
+    let average = start.elapsed() / iterations;
+
+    println!("{name:<32} {average:>12.2?}");
+
+    average
+}
+
+/// Random pixels, the worst case for downsampling
+fn noise(size: Size) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+
+    (0..size.width * size.height * 4)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// Lines of dark "text" on a light background, closer to a typical page
+fn page(size: Size) -> Vec<u8> {
+    let noise = noise(size);
+
+    (0..size.width * size.height)
+        .flat_map(|index| {
+            let (x, y) = (index % size.width, index / size.width);
+            let text = y % 24 < 12 && x % 9 < 6 && noise[index as usize].is_multiple_of(3);
+
+            match text {
+                true => [40, 30, 30, 255],
+                false => [250, 248, 245, 255],
+            }
+        })
+        .collect()
+}
+
+/// Average pixels one at a time, like `draw_background` used to
+fn reference(upper: &[u8], lower: &[u8], output: &mut [Color]) {
+    let pixel =
+        |bytes: &[u8], x: usize| Color::new(bytes[x * 4 + 2], bytes[x * 4 + 1], bytes[x * 4]);
+
+    for (x, color) in output.iter_mut().enumerate() {
+        *color = Color::average([pixel(upper, x), pixel(lower, x)]);
+    }
+}
+
+fn main() {
+    // A 1080p page, rendered to a terminal with cells of 2x4 pixels
+    let pixels = Size::new(1920, 1080);
+    let cells = Size::new(pixels.width / 2, pixels.height / 4);
+    let row = pixels.width as usize * 4;
+    let mut output = vec![Color::black(); pixels.width as usize];
+
+    for (name, bitmap) in [("noise", noise(pixels)), ("page", page(pixels))] {
+        println!("{name}, {}x{} pixels:", pixels.width, pixels.height);
+
+        let mut average = |kernel: fn(&[u8], &[u8], &mut [Color])| {
+            for rows in bitmap.chunks_exact(row * 2) {
+                kernel(&rows[..row], &rows[row..], black_box(&mut output));
+            }
+        };
+        let reference = measure("  reference", || average(reference));
+        let scalar = measure("  scalar", || average(average_rows_scalar));
+        let simd = measure("  dispatched", || average(average_rows));
+
+        println!(
+            "  speed-up: {:.2}x scalar, {:.2}x dispatched",
+            reference.as_secs_f64() / scalar.as_secs_f64(),
+            reference.as_secs_f64() / simd.as_secs_f64(),
+        );
+
+        let mut renderer = Renderer::with_painter(Painter::with_output(Box::new(std::io::sink())));
+
+        renderer.set_threads(1);
+        renderer.set_size(cells, Size::new(2.0, 4.0));
+
+        measure("  draw_background", || {
+            renderer.draw_background(
+                black_box(&bitmap),
+                pixels,
+                Rect::new(0, 0, pixels.width, pixels.height),
+            )
+        });
+    }
+}
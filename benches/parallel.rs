@@ -0,0 +1,101 @@
+//! Compare mapping on persistent workers with spawning a thread per item.
+//!
+//! Run with `cargo bench --bench parallel`.
+
+use std::{
+    hint::black_box,
+    panic, thread,
+    time::{Duration, Instant},
+};
+
+use carbonyl::utils::parallel_map;
+
+/// Time spent measuring each case
+const DURATION: Duration = Duration::from_secs(2);
+
+/// Average time of a call to `run`, repeated for about `DURATION`
+fn measure(name: &str, mut run: impl FnMut()) -> Duration {
+    // Warm up the workers
+    run();
+
+    let start = Instant::now();
+    let mut iterations = 0;
+
+    while start.elapsed() < DURATION {
+        run();
+        iterations += 1;
+    }
+
+    let average = start.elapsed() / iterations;
+
+    println!("{name:<32} {average:>12.2?}");
+
+    average
+}
+
+/// Map items on scoped threads, one per item, like `parallel_map` used to
+fn reference<I, T, F>(items: Vec<I>, map: F) -> Vec<T>
+where
+    I: Send,
+    T: Send,
+    F: Fn(I) -> T + Sync,
+{
+    let mut items = items.into_iter();
+    let Some(first) = items.next() else {
+        return Vec::new();
+    };
+    let map = &map;
+
+    thread::scope(|scope| {
+        let workers = items
+            .map(|item| scope.spawn(move || map(item)))
+            .collect::<Vec<_>>();
+        let mut output = Vec::with_capacity(workers.len() + 1);
+
+        output.push(map(first));
+
+        for worker in workers {
+            output.push(
+                worker
+                    .join()
+                    .unwrap_or_else(|error| panic::resume_unwind(error)),
+            )
+        }
+
+        output
+    })
+}
+
+/// Sum a band of cells, about the cost of encoding a band of a busy frame
+fn work(band: &[u32]) -> u64 {
+    band.iter().fold(0u64, |sum, &cell| {
+        sum.wrapping_mul(31).wrapping_add(cell as u64)
+    })
+}
+
+fn main() {
+    // As many bands as the renderer uses at most, even on fewer cores
+    let threads = 8;
+
+    println!("{threads} bands:");
+
+    // Bands of a 240x67 frame, then of a frame 16 times larger
+    for cells in [240 * 67_usize, 240 * 67 * 16] {
+        let frame = (0..cells as u32).collect::<Vec<_>>();
+        let bands = || frame.chunks(cells.div_ceil(threads)).collect::<Vec<_>>();
+
+        println!("  {cells} cells:");
+
+        let spawned = measure("    thread per item", || {
+            black_box(reference(bands(), work));
+        });
+        let pooled = measure("    parallel_map", || {
+            black_box(parallel_map(bands(), work));
+        });
+
+        println!(
+            "    speed-up: {:.2}x",
+            spawned.as_secs_f64() / pooled.as_secs_f64()
+        );
+    }
+}
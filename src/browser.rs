@@ -1,3 +1,5 @@
 mod bridge;
+mod dispatch;
 
 pub use bridge::*;
+pub use dispatch::*;
@@ -1,7 +1,7 @@
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
 use std::io::Write;
 use std::process::{Command, Stdio};
-use std::sync::{Mutex, mpsc};
+use std::sync::{Arc, Mutex};
 use std::{env, io, thread};
 
 use libc::{c_char, c_float, c_int, c_uchar, c_uint, c_void, size_t};
@@ -9,8 +9,10 @@ use libc::{c_char, c_float, c_int, c_uchar, c_uint, c_void, size_t};
 use crate::cli::{CommandLine, CommandLineProgram, EnvVar};
 use crate::gfx::{Cast, Color, Point, Rect, Size};
 use crate::input;
-use crate::output::{RenderThread, Window};
-use crate::ui::navigation::NavigationAction;
+use crate::output::{CursorShape, RenderThread, Renderer, Window};
+use crate::ui::keymap::Keymap;
+
+use super::{InputDispatch, SharedScale};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -49,6 +51,8 @@ pub struct CText {
 pub struct RendererBridge {
     cmd: CommandLine,
     window: Window,
+    /// Copy of `window.scale` readable without locking the bridge
+    scale: Arc<SharedScale>,
     renderer: RenderThread,
 }
 
@@ -90,17 +94,69 @@ impl From<CColor> for Color {
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct BrowserDelegate {
-    shutdown: extern "C" fn(),
-    refresh: extern "C" fn(),
-    go_to: extern "C" fn(*const c_char),
-    go_back: extern "C" fn(),
-    go_forward: extern "C" fn(),
-    scroll: extern "C" fn(c_int),
-    key_press: extern "C" fn(c_char),
-    mouse_up: extern "C" fn(c_uint, c_uint),
-    mouse_down: extern "C" fn(c_uint, c_uint),
-    mouse_move: extern "C" fn(c_uint, c_uint),
-    post_task: extern "C" fn(extern "C" fn(*mut c_void), *mut c_void),
+    pub shutdown: extern "C" fn(),
+    pub refresh: extern "C" fn(),
+    pub go_to: extern "C" fn(*const c_char),
+    pub go_back: extern "C" fn(),
+    pub go_forward: extern "C" fn(),
+    pub scroll: extern "C" fn(c_int),
+    pub key_press: extern "C" fn(c_char),
+    pub mouse_up: extern "C" fn(c_uint, c_uint),
+    pub mouse_down: extern "C" fn(c_uint, c_uint),
+    pub mouse_move: extern "C" fn(c_uint, c_uint),
+    pub post_task: extern "C" fn(extern "C" fn(*mut c_void), *mut c_void),
+    /// Called after the terminal was resized, the browser should
+    /// fetch the new size using `carbonyl_renderer_get_size`.
+    /// Optional, the browser handles SIGWINCH itself if missing.
+    pub resize: Option<extern "C" fn()>,
+    /// Called for every key event with its action (0 press, 1 repeat, 2 release),
+    /// DOM `key` and `code` values, inserted text code point (0 if none)
+    /// and modifiers mask (shift 1, alt 2, control 4, meta 8, other keys are left out).
+    /// Optional, keys are sent through `key_press` if missing.
+    pub key_event: Option<extern "C" fn(c_int, *const c_char, *const c_char, u32, c_uint)>,
+    /// Called to insert pasted text in the focused element of the page.
+    /// Optional, the text is typed key by key if missing.
+    pub paste: Option<extern "C" fn(*const c_char)>,
+    /// Called for every mouse event with its type (0 down, 1 up, 2 move), position,
+    /// button (0 left, 1 middle, 2 right, 3 back, 4 forward, -1 if none)
+    /// and modifiers mask (shift 1, alt 2, control 4, other keys are left out).
+    /// Moves with a button are drags. Optional, `mouse_down`, `mouse_up`
+    /// and `mouse_move` are called with the position only if missing.
+    pub mouse_event: Option<extern "C" fn(c_int, c_uint, c_uint, c_int, c_uint)>,
+    /// Called to scroll horizontally, positive values scroll left. Optional.
+    pub horizontal_scroll: Option<extern "C" fn(c_int)>,
+    /// Called when the terminal window gains or loses focus,
+    /// to focus or blur the page. Optional.
+    pub focus: Option<extern "C" fn(bool)>,
+    /// Called to copy the page selection, which is sent back
+    /// using `carbonyl_renderer_set_clipboard`. Optional.
+    pub copy: Option<extern "C" fn()>,
+    /// Called to zoom the page in (1), out (-1) or reset it (0). Optional.
+    pub zoom: Option<extern "C" fn(c_int)>,
+    /// Called to find the next occurrence of the page selection.
+    /// Optional, the key is sent to the page otherwise.
+    pub find: Option<extern "C" fn()>,
+    /// Called with whether the terminal background is dark,
+    /// to set `prefers-color-scheme`. Optional.
+    pub color_scheme: Option<extern "C" fn(bool)>,
+}
+
+impl BrowserDelegate {
+    /// Run a closure on the browser thread using `post_task`
+    pub fn post<F>(&self, run: F)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let closure: *mut Box<dyn FnMut()> = Box::into_raw(Box::new(Box::new(run)));
+
+        (self.post_task)(post_task_handler, closure as *mut c_void);
+    }
+}
+
+extern "C" fn post_task_handler(callback: *mut c_void) {
+    let mut closure = unsafe { Box::from_raw(callback as *mut Box<dyn FnMut()>) };
+
+    closure()
 }
 
 fn main() -> io::Result<Option<i32>> {
@@ -113,9 +169,33 @@ fn main() -> io::Result<Option<i32>> {
         return Ok(None);
     }
 
+    // Report problems before the terminal switches to the alternate screen
+    for error in &cmd.errors {
+        eprintln!("carbonyl: {error}")
+    }
+
+    for error in Keymap::load(cmd.keymap.clone()).errors() {
+        eprintln!("carbonyl: keymap: {error}")
+    }
+
     let mut terminal = input::Terminal::setup();
     let mut command = Command::new(env::current_exe()?);
 
+    if terminal.pixel_mouse() {
+        command.env(EnvVar::PixelMouse, "1");
+    }
+
+    if !terminal.widths().is_empty() {
+        command.env(EnvVar::Widths, terminal.widths().to_string());
+    }
+
+    if let Some(cell) = terminal.reported_cell_pixels() {
+        command.env(
+            EnvVar::CellPixels,
+            format!("{}x{}", cell.width, cell.height),
+        );
+    }
+
     if !cmd.bitmap {
         command
             .arg("--disable-threaded-scrolling")
@@ -160,10 +240,26 @@ pub extern "C" fn carbonyl_bridge_get_dpi() -> c_float {
 
 #[unsafe(no_mangle)]
 pub extern "C" fn carbonyl_renderer_create() -> RendererPtr {
+    let window = Window::read();
+    let cmd = CommandLine::parse();
+
+    // Options set by the parent process, shown with its output in debug mode
+    for error in &cmd.errors {
+        eprintln!("carbonyl: {error}")
+    }
+
+    // Loaded once, errors were reported by the parent process
+    let keymap = Keymap::load(cmd.keymap.clone());
     let bridge = RendererBridge {
-        cmd: CommandLine::parse(),
-        window: Window::read(),
-        renderer: RenderThread::new(),
+        cmd,
+        scale: Arc::new(SharedScale::new(window.scale)),
+        window,
+        renderer: RenderThread::with_renderer(move || {
+            let mut renderer = Renderer::new();
+
+            renderer.set_keymap(keymap);
+            renderer
+        }),
     };
 
     Box::into_raw(Box::new(Mutex::new(bridge)))
@@ -188,13 +284,15 @@ pub unsafe extern "C" fn carbonyl_renderer_resize(bridge: RendererPtr) {
     let bridge = unsafe { bridge.as_ref() };
     let mut bridge = bridge.unwrap().lock().unwrap();
     let window = bridge.window.update();
-    let cells = window.cells;
+    let (cells, sampling, scale) = (window.cells, window.sampling, window.scale);
 
     tracing::debug!("resizing renderer, terminal window: {:?}", window);
 
+    bridge.scale.store(scale);
+
     bridge
         .renderer
-        .render(move |renderer| renderer.set_size(cells));
+        .render(move |renderer| renderer.set_size(cells, sampling));
 }
 
 #[unsafe(no_mangle)]
@@ -222,6 +320,23 @@ pub unsafe extern "C" fn carbonyl_renderer_set_title(bridge: RendererPtr, title:
         .render(move |renderer| renderer.set_title(title.to_str().unwrap()).unwrap());
 }
 
+/// Function called by the C++ code when the page writes to the clipboard,
+/// after a copy command or when the selection is copied.
+///
+/// # Safety
+///
+/// `bridge` must be a pointer returned by `carbonyl_renderer_create`,
+/// and `text` a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn carbonyl_renderer_set_clipboard(bridge: RendererPtr, text: *const c_char) {
+    let (bridge, text) = unsafe { (bridge.as_ref(), CStr::from_ptr(text)) };
+    let (mut bridge, text) = (bridge.unwrap().lock().unwrap(), text.to_owned());
+
+    bridge
+        .renderer
+        .render(move |renderer| renderer.set_clipboard(&text.to_string_lossy()).unwrap());
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn carbonyl_renderer_draw_text(
     bridge: RendererPtr,
@@ -253,6 +368,45 @@ pub unsafe extern "C" fn carbonyl_renderer_draw_text(
     });
 }
 
+/// Function called by the C++ code when the caret of the focused text input changes.
+///
+/// `rect` is in browser pixels, the terminal cursor is hidden if `visible` is false.
+///
+/// # Safety
+///
+/// `bridge` must be a pointer returned by `carbonyl_renderer_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn carbonyl_renderer_set_caret(
+    bridge: RendererPtr,
+    rect: CRect,
+    visible: bool,
+) {
+    let bridge = unsafe { bridge.as_ref() };
+    let mut bridge = bridge.unwrap().lock().unwrap();
+    let scale = bridge.window.scale;
+    let caret = visible.then(|| {
+        let origin = Point::<f32>::from(rect.origin);
+        let size = Size::<f32>::from(rect.size);
+        // Use the vertical center of the caret, and skip the navigation bar
+        let cursor = ((origin + (0.0, size.height / 2.0)) / (scale.width, scale.height))
+            .floor()
+            .cast::<i32>()
+            + (0, 1);
+        // Wide carets are used for overtype mode
+        let shape = if size.width > scale.width {
+            CursorShape::Block
+        } else {
+            CursorShape::Bar
+        };
+
+        (cursor, shape)
+    });
+
+    bridge
+        .renderer
+        .render(move |renderer| renderer.set_caret(caret));
+}
+
 #[derive(Clone, Copy)]
 struct CallbackData(*const c_void);
 
@@ -303,21 +457,6 @@ pub unsafe extern "C" fn carbonyl_renderer_get_size(bridge: RendererPtr) -> CSiz
     bridge.window.browser.into()
 }
 
-extern "C" fn post_task_handler(callback: *mut c_void) {
-    let mut closure = unsafe { Box::from_raw(callback as *mut Box<dyn FnMut()>) };
-
-    closure()
-}
-
-unsafe fn post_task<F>(handle: extern "C" fn(extern "C" fn(*mut c_void), *mut c_void), run: F)
-where
-    F: FnMut() + Send + 'static,
-{
-    let closure: *mut Box<dyn FnMut()> = Box::into_raw(Box::new(Box::new(run)));
-
-    handle(post_task_handler, closure as *mut c_void);
-}
-
 /// Function called by the C++ code to listen for input events.
 ///
 /// This will block so the calling code should start and own a dedicated thread.
@@ -333,106 +472,62 @@ pub unsafe extern "C" fn carbonyl_renderer_listen(
     use input::*;
 
     thread::spawn(move || {
-        macro_rules! emit {
-            ($event:ident($($args:expr),*) => $closure:expr) => {{
-                let run = move || {
-                    (delegate.$event)($($args),*);
-
-                    $closure
-                };
-
-                unsafe { post_task(delegate.post_task, run) }
-            }};
-            ($event:ident($($args:expr),*)) => {{
-                emit!($event($($args),*) => {})
-            }};
+        // Let the browser handle resizes if it can't be notified
+        let chain = delegate.resize.is_none();
+        let watched = watch_resize(chain, || {
+            let mut bridge = bridge.lock().unwrap();
+            let window = bridge.window.update();
+            let (cells, sampling, scale) = (window.cells, window.sampling, window.scale);
+
+            tracing::debug!("terminal resized, terminal window: {:?}", window);
+
+            bridge.scale.store(scale);
+
+            bridge
+                .renderer
+                .render(move |renderer| renderer.set_size(cells, sampling));
+
+            if let Some(resize) = delegate.resize {
+                delegate.post(move || resize())
+            }
+        });
+
+        if let Err(error) = watched {
+            tracing::error!("Failed to watch terminal resizes: {error}");
         }
+    });
 
-        listen(|mut events| {
-            bridge.lock().unwrap().renderer.render(move |renderer| {
-                let get_scale = || bridge.lock().unwrap().window.scale;
-                let scale = |col, row| {
-                    let scale = get_scale();
-
-                    scale
-                        .mul(((col as f32 + 0.5), (row as f32 - 0.5)))
-                        .floor()
-                        .cast()
-                        .into()
-                };
-                let dispatch = |action| {
-                    match action {
-                        NavigationAction::Ignore => (),
-                        NavigationAction::Forward => return true,
-                        NavigationAction::GoBack() => emit!(go_back()),
-                        NavigationAction::GoForward() => emit!(go_forward()),
-                        NavigationAction::Refresh() => emit!(refresh()),
-                        NavigationAction::GoTo(url) => {
-                            let c_str = CString::new(url).unwrap();
-
-                            emit!(go_to(c_str.as_ptr()))
-                        }
-                    };
-
-                    false
-                };
-
-                for event in std::mem::take(&mut events) {
-                    use Event::*;
-
-                    match event {
-                        Exit => (),
-                        Scroll { delta } => {
-                            let scale = get_scale();
-
-                            emit!(scroll((delta as f32 * scale.height) as c_int))
-                        }
-                        KeyPress { key } => {
-                            if dispatch(renderer.keypress(&key).unwrap()) {
-                                emit!(key_press(key.char as c_char))
-                            }
-                        }
-                        MouseUp { col, row } => {
-                            if dispatch(renderer.mouse_up((col as _, row as _).into()).unwrap()) {
-                                let (width, height) = scale(col, row);
-
-                                emit!(mouse_up(width, height))
-                            }
-                        }
-                        MouseDown { col, row } => {
-                            if dispatch(renderer.mouse_down((col as _, row as _).into()).unwrap()) {
-                                let (width, height) = scale(col, row);
-
-                                emit!(mouse_down(width, height))
-                            }
-                        }
-                        MouseMove { col, row } => {
-                            if dispatch(renderer.mouse_move((col as _, row as _).into()).unwrap()) {
-                                let (width, height) = scale(col, row);
-
-                                emit!(mouse_move(width, height))
-                            }
-                        }
-                        Terminal(terminal) => match terminal {
-                            TerminalEvent::Name(name) => tracing::debug!("terminal name: {name}"),
-                            TerminalEvent::TrueColorSupported => renderer.enable_true_color(),
-                        },
-                    }
-                }
-            })
-        })
-        .unwrap();
+    let (dispatch, cmd) = {
+        let bridge = bridge.lock().unwrap();
+        let dispatch = InputDispatch::new(bridge.renderer.sender(), bridge.scale.clone(), delegate);
 
-        // Setup single-use channel
-        let (tx, rx) = mpsc::channel();
+        (dispatch, bridge.cmd.clone())
+    };
+
+    thread::spawn(move || {
+        let mut recorder = cmd
+            .record_input
+            .map(|path| Recorder::create(&path).unwrap());
+        let mut dispatch = |events: Vec<Event>| {
+            if let Some(recorder) = &mut recorder {
+                recorder.record(&events).unwrap()
+            }
+
+            dispatch.dispatch(events)
+        };
+        // Keep listening to the terminal once the replay is over
+        if let Some(path) = cmd.replay_input {
+            replay(
+                io::BufReader::new(std::fs::File::open(path).unwrap()),
+                cmd.replay_speed,
+                &mut dispatch,
+            )
+            .unwrap()
+        }
 
-        // Signal the browser to shutdown and notify our thread
-        emit!(shutdown() => tx.send(()).unwrap());
-        rx.recv().unwrap();
+        // Quitting is handled by the dispatch, this only stops if stdin fails
+        let Err(error) = listen(cmd.pixel_mouse, &mut dispatch);
 
-        // Shutdown rendering thread
-        // if let Some(handle) = { bridge.lock().unwrap().renderer().stop() } {
-        //     handle.join().unwrap()
-        // }
+        tracing::error!("Failed to read terminal input: {error}");
     });
 }
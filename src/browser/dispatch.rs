@@ -0,0 +1,348 @@
+use std::ffi::CString;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libc::{c_char, c_int, c_uint};
+
+use crate::gfx::Size;
+use crate::input::{
+    Event, Key, KeyAction, KeyCode, KeyModifiers, MouseButton, NamedKey, TerminalEvent,
+};
+use crate::output::{RenderSender, Renderer};
+use crate::ui::navigation::{ColorScheme, NavigationAction};
+
+use super::BrowserDelegate;
+
+/// Size of a terminal cell in browser pixels, shared between threads without locking.
+///
+/// Both dimensions are stored in a single atomic, so a snapshot is always consistent.
+pub struct SharedScale(AtomicU64);
+
+impl SharedScale {
+    pub fn new(scale: Size<f32>) -> SharedScale {
+        SharedScale(AtomicU64::new(Self::pack(scale)))
+    }
+
+    pub fn load(&self) -> Size<f32> {
+        let value = self.0.load(Ordering::Acquire);
+
+        Size::new(
+            f32::from_bits((value >> 32) as u32),
+            f32::from_bits(value as u32),
+        )
+    }
+
+    pub fn store(&self, scale: Size<f32>) {
+        self.0.store(Self::pack(scale), Ordering::Release)
+    }
+
+    fn pack(scale: Size<f32>) -> u64 {
+        (scale.width.to_bits() as u64) << 32 | scale.height.to_bits() as u64
+    }
+}
+
+macro_rules! emit {
+    ($delegate:expr, $event:ident($($args:expr),*)) => {{
+        let delegate = $delegate;
+
+        delegate.post(move || (delegate.$event)($($args),*))
+    }};
+}
+
+/// Forward terminal input to the renderer and the browser.
+///
+/// Events are handled on the rendering thread, dispatching never blocks
+/// on it and never locks the renderer bridge.
+#[derive(Clone)]
+pub struct InputDispatch {
+    renderer: RenderSender,
+    scale: Arc<SharedScale>,
+    delegate: BrowserDelegate,
+}
+
+impl InputDispatch {
+    pub fn new(
+        renderer: RenderSender,
+        scale: Arc<SharedScale>,
+        delegate: BrowserDelegate,
+    ) -> InputDispatch {
+        InputDispatch {
+            renderer,
+            scale,
+            delegate,
+        }
+    }
+
+    /// Queue a batch of events, returns without waiting for them to be handled.
+    pub fn dispatch(&self, events: Vec<Event>) {
+        let InputDispatch {
+            ref scale,
+            delegate,
+            ..
+        } = *self;
+        let scale = scale.clone();
+        let mut events = Some(events);
+
+        self.renderer.render(move |renderer| {
+            // Use the same scale for the whole batch
+            let scale = scale.load();
+            // Positions are in cells, the first row holds the navigation bar
+            let position = |col: f32, row: f32| {
+                let (x, y) = scale.mul((col, row - 1.0)).floor().cast::<u32>().into();
+
+                (x, y)
+            };
+            // Scroll by a page, minus a line of context
+            let page = |renderer: &Renderer| {
+                renderer.get_size().height.saturating_sub(1) as f32 * scale.height
+            };
+            let dispatch = |action| {
+                match action {
+                    NavigationAction::Ignore => (),
+                    NavigationAction::Forward => return true,
+                    NavigationAction::GoBack() => emit!(delegate, go_back()),
+                    NavigationAction::GoForward() => emit!(delegate, go_forward()),
+                    NavigationAction::Refresh() => emit!(delegate, refresh()),
+                    NavigationAction::GoTo(url) => {
+                        let c_str = CString::new(url).unwrap();
+
+                        emit!(delegate, go_to(c_str.as_ptr()))
+                    }
+                    // The page sends its selection back using `carbonyl_renderer_set_clipboard`
+                    NavigationAction::CopySelection() => match delegate.copy {
+                        Some(copy) => delegate.post(move || copy()),
+                        None => return true,
+                    },
+                    NavigationAction::Quit() => emit!(delegate, shutdown()),
+                    NavigationAction::ZoomIn() => Self::zoom(delegate, 1),
+                    NavigationAction::ZoomOut() => Self::zoom(delegate, -1),
+                    NavigationAction::ZoomReset() => Self::zoom(delegate, 0),
+                    // Let the page handle the key if the browser can't
+                    NavigationAction::Find() => match delegate.find {
+                        Some(find) => delegate.post(move || find()),
+                        None => return true,
+                    },
+                    // Handled by the renderer
+                    NavigationAction::Copy(_)
+                    | NavigationAction::ReadClipboard()
+                    | NavigationAction::CycleColorFilter() => (),
+                };
+
+                false
+            };
+
+            for event in events.take().unwrap_or_default() {
+                use Event::*;
+
+                // Clipboard content read from the terminal is pasted
+                let event = match event {
+                    Terminal(TerminalEvent::Clipboard(text)) => Paste(text),
+                    event => event,
+                };
+
+                match event {
+                    Scroll { delta } => {
+                        emit!(delegate, scroll((delta as f32 * scale.height) as c_int))
+                    }
+                    HorizontalScroll { delta } => match delegate.horizontal_scroll {
+                        // Scroll as far as vertically, cells are taller than wide
+                        Some(scroll) => {
+                            let delta = (delta as f32 * scale.height) as c_int;
+
+                            delegate.post(move || scroll(delta))
+                        }
+                        None => tracing::debug!("ignoring horizontal scroll: {delta}"),
+                    },
+                    KeyPress { key } => {
+                        // Releases go straight to the browser
+                        if key.action == KeyAction::Release
+                            || dispatch(renderer.keypress(&key).unwrap())
+                        {
+                            Self::send_key(delegate, key, page(renderer))
+                        }
+                    }
+                    Paste(text) => {
+                        if !dispatch(renderer.paste(&text).unwrap()) {
+                            continue;
+                        }
+
+                        if let Some(paste) = delegate.paste {
+                            let c_str = CString::new(text.replace('\0', "")).unwrap();
+
+                            delegate.post(move || paste(c_str.as_ptr()));
+
+                            continue;
+                        }
+
+                        // Type the text if the browser can't paste it
+                        for char in text.chars() {
+                            let key = match char {
+                                '\n' => Key::named(NamedKey::Enter, Default::default()),
+                                '\t' => Key::named(NamedKey::Tab, Default::default()),
+                                char => Key::char(char, Default::default()),
+                            };
+
+                            Self::send_key(delegate, key, page(renderer))
+                        }
+                    }
+                    MouseUp {
+                        col,
+                        row,
+                        button,
+                        modifiers,
+                    } => {
+                        if dispatch(renderer.mouse_up((col as _, row as _).into()).unwrap()) {
+                            let position = position(col, row);
+
+                            Self::send_mouse(
+                                delegate,
+                                MouseAction::Up,
+                                position,
+                                Some(button),
+                                modifiers,
+                            )
+                        }
+                    }
+                    MouseDown {
+                        col,
+                        row,
+                        button,
+                        modifiers,
+                    } => {
+                        if dispatch(renderer.mouse_down((col as _, row as _).into()).unwrap()) {
+                            let position = position(col, row);
+
+                            Self::send_mouse(
+                                delegate,
+                                MouseAction::Down,
+                                position,
+                                Some(button),
+                                modifiers,
+                            )
+                        }
+                    }
+                    MouseMove {
+                        col,
+                        row,
+                        button,
+                        modifiers,
+                    } => {
+                        if dispatch(renderer.mouse_move((col as _, row as _).into()).unwrap()) {
+                            let position = position(col, row);
+
+                            Self::send_mouse(
+                                delegate,
+                                MouseAction::Move,
+                                position,
+                                button,
+                                modifiers,
+                            )
+                        }
+                    }
+                    Focus { focused } => {
+                        renderer.set_focused(focused);
+
+                        if let Some(focus) = delegate.focus {
+                            delegate.post(move || focus(focused))
+                        }
+                    }
+                    Terminal(terminal) => match terminal {
+                        TerminalEvent::Name(name) => tracing::debug!("terminal name: {name}"),
+                        TerminalEvent::TrueColorSupported => renderer.enable_true_color(),
+                        TerminalEvent::ForegroundColor(color) => {
+                            renderer.set_terminal_foreground(color)
+                        }
+                        TerminalEvent::BackgroundColor(color) => {
+                            renderer.set_terminal_background(color);
+
+                            if let Some(color_scheme) = delegate.color_scheme {
+                                let scheme = ColorScheme::from_background(color);
+
+                                tracing::debug!("terminal color scheme: {scheme:?}");
+
+                                delegate.post(move || color_scheme(scheme == ColorScheme::Dark))
+                            }
+                        }
+                        reply => tracing::debug!("ignoring terminal reply: {reply:?}"),
+                    },
+                }
+            }
+        });
+    }
+
+    /// Send a key to the browser, `page` is the scroll distance for page keys in pixels
+    fn send_key(delegate: BrowserDelegate, key: Key, page: f32) {
+        if let Some(key_event) = delegate.key_event {
+            let dom_key = CString::new(key.dom_key().as_ref()).unwrap_or_default();
+            let code = CString::new(key.dom_code()).unwrap_or_default();
+            let text = key.text().map_or(0, u32::from);
+            let modifiers = key.modifiers.mask() & KEY_MODIFIERS;
+
+            return delegate.post(move || {
+                key_event(
+                    key.action as c_int,
+                    dom_key.as_ptr(),
+                    code.as_ptr(),
+                    text,
+                    modifiers,
+                )
+            });
+        }
+
+        // The legacy callback sends a press and a release for each key press
+        if key.action == KeyAction::Release {
+            return;
+        }
+
+        match (key.code, key.legacy_byte()) {
+            (_, Some(byte)) => emit!(delegate, key_press(byte as c_char)),
+            (KeyCode::Named(NamedKey::PageUp), _) => emit!(delegate, scroll(page as c_int)),
+            (KeyCode::Named(NamedKey::PageDown), _) => emit!(delegate, scroll(-page as c_int)),
+            (code, None) => tracing::debug!("ignoring key: {code:?}"),
+        }
+    }
+
+    /// Zoom the page in with a positive `step`, out with a negative one, or reset it
+    fn zoom(delegate: BrowserDelegate, step: c_int) {
+        match delegate.zoom {
+            Some(zoom) => delegate.post(move || zoom(step)),
+            None => tracing::debug!("ignoring zoom: {step}"),
+        }
+    }
+
+    /// Send a mouse event to the browser, `button` is the button pressed or released,
+    /// or held down while moving.
+    fn send_mouse(
+        delegate: BrowserDelegate,
+        action: MouseAction,
+        (x, y): (c_uint, c_uint),
+        button: Option<MouseButton>,
+        modifiers: KeyModifiers,
+    ) {
+        let Some(mouse_event) = delegate.mouse_event else {
+            return match action {
+                MouseAction::Down => emit!(delegate, mouse_down(x, y)),
+                MouseAction::Up => emit!(delegate, mouse_up(x, y)),
+                MouseAction::Move => emit!(delegate, mouse_move(x, y)),
+            };
+        };
+        let button = button.map_or(-1, |button| button as c_int);
+        let modifiers = modifiers.mask() & MOUSE_MODIFIERS;
+
+        delegate.post(move || mouse_event(action as c_int, x, y, button, modifiers))
+    }
+}
+
+/// Modifiers documented for `key_event`: shift, alt, control and meta.
+/// Hyper and lock keys aren't sent to the browser.
+const KEY_MODIFIERS: c_uint = 0b1111;
+/// Modifiers documented for `mouse_event`: shift, alt and control
+const MOUSE_MODIFIERS: c_uint = 0b111;
+
+/// Type of mouse event sent to the browser
+#[derive(Clone, Copy)]
+enum MouseAction {
+    Down = 0,
+    Up = 1,
+    Move = 2,
+}
@@ -23,4 +23,8 @@ void CarbonylRenderServiceImpl::DrawText(std::vector<mojom::TextDataPtr> data) {
     Renderer::GetCurrent()->DrawText(mapped);
 }
 
+void CarbonylRenderServiceImpl::SetCaret(const gfx::Rect& bounds, bool visible) {
+    Renderer::GetCurrent()->SetCaret(bounds, visible);
+}
+
 }
@@ -18,6 +18,7 @@ class CARBONYL_RENDERER_EXPORT CarbonylRenderServiceImpl: public mojom::Carbonyl
 
   // carbonyl::mojom::CarbonylRenderService:
   void DrawText(std::vector<mojom::TextDataPtr> data) override;
+  void SetCaret(const gfx::Rect& bounds, bool visible) override;
 
  private:
   mojo::Receiver<mojom::CarbonylRenderService> receiver_;
@@ -1,5 +1,6 @@
 #include "carbonyl/src/browser/renderer.h"
 
+#include <algorithm>
 #include <memory>
 #include <iostream>
 #include <stdio.h>
@@ -44,6 +45,7 @@ void carbonyl_renderer_resize(struct carbonyl_renderer* renderer);
 struct carbonyl_renderer_size carbonyl_renderer_get_size(struct carbonyl_renderer* renderer);
 void carbonyl_renderer_push_nav(struct carbonyl_renderer* renderer, const char* url, bool can_go_back, bool can_go_forward);
 void carbonyl_renderer_set_title(struct carbonyl_renderer* renderer, const char* title);
+void carbonyl_renderer_set_clipboard(struct carbonyl_renderer* renderer, const char* text);
 void carbonyl_renderer_clear_text(struct carbonyl_renderer* renderer);
 void carbonyl_renderer_listen(struct carbonyl_renderer* renderer, const struct carbonyl_renderer_browser_delegate* delegate);
 void carbonyl_renderer_draw_text(
@@ -51,6 +53,11 @@ void carbonyl_renderer_draw_text(
     const struct carbonyl_renderer_text* text,
     size_t text_size
 );
+void carbonyl_renderer_set_caret(
+    struct carbonyl_renderer* renderer,
+    const struct carbonyl_renderer_rect rect,
+    bool visible
+);
 void carbonyl_renderer_draw_bitmap(
     struct carbonyl_renderer* renderer,
     const unsigned char* pixels,
@@ -126,6 +133,10 @@ void Renderer::SetTitle(const std::string& title) {
     carbonyl_renderer_set_title(ptr_, title.c_str());
 }
 
+void Renderer::SetClipboard(const std::string& text) {
+    carbonyl_renderer_set_clipboard(ptr_, text.c_str());
+}
+
 void Renderer::DrawText(const std::vector<Text>& text) {
     struct carbonyl_renderer_text data[text.size()];
 
@@ -143,6 +154,23 @@ void Renderer::DrawText(const std::vector<Text>& text) {
     carbonyl_renderer_draw_text(ptr_, data, text.size());
 }
 
+void Renderer::SetCaret(const gfx::Rect& rect, bool visible) {
+    carbonyl_renderer_set_caret(
+        ptr_,
+        {
+            .origin = {
+                .x = (unsigned int)std::max(rect.x(), 0),
+                .y = (unsigned int)std::max(rect.y(), 0),
+            },
+            .size = {
+                .width = (unsigned int)rect.width(),
+                .height = (unsigned int)rect.height(),
+            },
+        },
+        visible
+    );
+}
+
 void Renderer::DrawBitmap(
     const unsigned char* pixels,
     const gfx::Size& pixels_size,
@@ -23,6 +23,16 @@ struct carbonyl_renderer_browser_delegate {
     void (*mouse_down) (unsigned int, unsigned int);
     void (*mouse_move) (unsigned int, unsigned int);
     void (*post_task) (void (*)(void*), void*);
+    void (*resize) ();
+    void (*key_event) (int action, const char* key, const char* code, uint32_t text, unsigned int modifiers);
+    void (*paste) (const char* text);
+    void (*mouse_event) (int type, unsigned int x, unsigned int y, int button, unsigned int modifiers);
+    void (*horizontal_scroll) (int);
+    void (*focus) (bool);
+    void (*copy) ();
+    void (*zoom) (int);
+    void (*find) ();
+    void (*color_scheme) (bool dark);
 };
 
 } /* end extern "C" */
@@ -57,7 +67,9 @@ public:
     void Listen(const struct carbonyl_renderer_browser_delegate* delegate);
     void PushNav(const std::string& url, bool can_go_back, bool can_go_forward);
     void SetTitle(const std::string& title);
+    void SetClipboard(const std::string& text);
     void DrawText(const std::vector<Text>& text);
+    void SetCaret(const gfx::Rect& rect, bool visible);
     void DrawBitmap(
         const unsigned char* pixels,
         const gfx::Size& size,
@@ -1,6 +1,10 @@
-use std::{env, ffi::OsStr};
+use std::{env, ffi::OsStr, path::PathBuf};
 
 use super::CommandLineProgram;
+use crate::{
+    gfx::Size,
+    output::{ColorFilter, Widths},
+};
 
 #[derive(Clone, Debug)]
 pub struct CommandLine {
@@ -9,14 +13,36 @@ pub struct CommandLine {
     pub zoom: f32,
     pub debug: bool,
     pub bitmap: bool,
+    pub color_filter: ColorFilter,
     pub program: CommandLineProgram,
     pub shell_mode: bool,
+    /// Whether the terminal reports mouse positions in pixels
+    pub pixel_mouse: bool,
+    /// Width of the graphemes the terminal measures differently than `unicode-width`
+    pub widths: Widths,
+    /// Cell size in pixels reported by the terminal, when TIOCGWINSZ doesn't
+    pub cell_pixels: Option<Size<f32>>,
+    /// Keymap file, loaded once with `Keymap::load` by whoever needs it
+    pub keymap: Option<PathBuf>,
+    /// Whether the quit shortcut must be pressed twice
+    pub confirm_quit: bool,
+    /// File to record input events to
+    pub record_input: Option<PathBuf>,
+    /// File to replay input events from, before reading the terminal
+    pub replay_input: Option<PathBuf>,
+    /// Speed factor of the replay, 0 to replay without delays
+    pub replay_speed: f32,
+    /// Invalid options and environment variables, reported on stderr at startup
+    pub errors: Vec<String>,
 }
 
 pub enum EnvVar {
     Debug,
     Bitmap,
     ShellMode,
+    PixelMouse,
+    Widths,
+    CellPixels,
 }
 
 impl EnvVar {
@@ -25,6 +51,9 @@ impl EnvVar {
             EnvVar::Debug => "CARBONYL_ENV_DEBUG",
             EnvVar::Bitmap => "CARBONYL_ENV_BITMAP",
             EnvVar::ShellMode => "CARBONYL_ENV_SHELL_MODE",
+            EnvVar::PixelMouse => "CARBONYL_ENV_PIXEL_MOUSE",
+            EnvVar::Widths => "CARBONYL_ENV_WIDTHS",
+            EnvVar::CellPixels => "CARBONYL_ENV_CELL_PIXELS",
         }
     }
 }
@@ -41,8 +70,15 @@ impl CommandLine {
         let mut zoom = 1.0;
         let mut debug = false;
         let mut bitmap = false;
+        let mut color_filter = ColorFilter::None;
         let mut shell_mode = false;
         let mut program = CommandLineProgram::Main;
+        let mut keymap = None;
+        let mut confirm_quit = false;
+        let mut record_input = None;
+        let mut replay_input = None;
+        let mut replay_speed = 1.0;
+        let mut errors = Vec::new();
         let args = env::args().skip(1).collect::<Vec<String>>();
 
         for arg in &args {
@@ -77,6 +113,16 @@ impl CommandLine {
                 "-z" | "--zoom" => set_f32!(zoom = zoom / 100.0),
                 "-d" | "--debug" => set!(debug, Debug),
                 "-b" | "--bitmap" => set!(bitmap, Bitmap),
+                "--color-filter" => match value.map(|value| value.parse()) {
+                    Some(Ok(filter)) => color_filter = filter,
+                    Some(Err(error)) => errors.push(error),
+                    None => (),
+                },
+                "--keymap" => keymap = value.map(PathBuf::from),
+                "--confirm-quit" => confirm_quit = true,
+                "--record-input" => record_input = value.map(PathBuf::from),
+                "--replay-input" => replay_input = value.map(PathBuf::from),
+                "--replay-speed" => set_f32!(replay_speed = replay_speed.max(0.0)),
 
                 "-h" | "--help" => program = CommandLineProgram::Help,
                 "-v" | "--version" => program = CommandLineProgram::Version,
@@ -96,14 +142,41 @@ impl CommandLine {
             shell_mode = true;
         }
 
+        let pixel_mouse = env::var(EnvVar::PixelMouse).is_ok();
+        let widths = match env::var(EnvVar::Widths).map(|widths| widths.parse()) {
+            Ok(Ok(widths)) => widths,
+            Ok(Err(error)) => {
+                errors.push(error);
+
+                Widths::default()
+            }
+            Err(_) => Widths::default(),
+        };
+        // Written as `{width}x{height}`
+        let cell_pixels = env::var(EnvVar::CellPixels).ok().and_then(|size| {
+            let (width, height) = size.split_once('x')?;
+
+            Some(Size::new(width.parse().ok()?, height.parse().ok()?))
+        });
+
         CommandLine {
             args,
             fps,
             zoom,
             debug,
             bitmap,
+            color_filter,
             program,
             shell_mode,
+            pixel_mouse,
+            widths,
+            cell_pixels,
+            keymap,
+            confirm_quit,
+            record_input,
+            replay_input,
+            replay_speed,
+            errors,
         }
     }
 }
@@ -1,7 +1,11 @@
+use std::sync::LazyLock;
+
 use super::Vector3;
 use crate::impl_vector_overload;
 
+/// An RGB color, laid out as `r`, `g`, `b` in memory.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
 pub struct Color<T: Copy = u8> {
     pub r: T,
     pub g: T,
@@ -21,6 +25,90 @@ impl Color {
     pub fn black() -> Color {
         Color::<u8>::new(0, 0, 0)
     }
+
+    /// Pack into a single integer, as `0xRRGGBB`
+    pub fn pack(&self) -> u32 {
+        u32::from_be_bytes([0, self.r, self.g, self.b])
+    }
+
+    /// Unpack a color packed using [`Color::pack`]
+    pub fn unpack(value: u32) -> Color {
+        let [_, r, g, b] = value.to_be_bytes();
+
+        Color::new(r, g, b)
+    }
+
+    /// Convert from sRGB to linear light, see [`LINEAR_MAX`].
+    pub fn to_linear(&self) -> Color<u16> {
+        self.map(|value| GAMMA.to_linear[value as usize])
+    }
+
+    /// Relative luminance, from 0 for black to 1 for white
+    pub fn luminance(&self) -> f32 {
+        let Color { r, g, b } = self.to_linear().cast::<f32>();
+
+        (0.2126 * r + 0.7152 * g + 0.0722 * b) / LINEAR_MAX as f32
+    }
+
+    /// Average colors in linear light.
+    ///
+    /// Averaging sRGB values directly makes mixed colors too dark,
+    /// turning black text on a white background into muddy gray blocks.
+    pub fn average<const N: usize>(colors: [Color; N]) -> Color {
+        let sum = colors.iter().fold(Color::<u32>::splat(0), |sum, color| {
+            sum + color.to_linear().cast::<u32>()
+        });
+
+        (sum / N as u32).cast::<u16>().to_srgb()
+    }
 }
 
+impl Color<u16> {
+    /// Convert from linear light back to sRGB, see [`LINEAR_MAX`].
+    pub fn to_srgb(&self) -> Color {
+        self.map(|value| GAMMA.to_srgb[value.min(LINEAR_MAX) as usize])
+    }
+}
+
+/// Maximum value of a linear light channel.
+/// Uses 12 bits to keep enough precision in dark colors.
+pub const LINEAR_MAX: u16 = 4095;
+
+/// Precomputed sRGB transfer function lookup tables
+struct Gamma {
+    to_linear: [u16; 256],
+    to_srgb: [u8; LINEAR_MAX as usize + 1],
+}
+
+static GAMMA: LazyLock<Gamma> = LazyLock::new(|| {
+    let mut gamma = Gamma {
+        to_linear: [0; 256],
+        to_srgb: [0; LINEAR_MAX as usize + 1],
+    };
+
+    for (srgb, linear) in gamma.to_linear.iter_mut().enumerate() {
+        let value = srgb as f32 / 255.0;
+        let value = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+
+        *linear = (value * LINEAR_MAX as f32).round() as u16;
+    }
+
+    for (linear, srgb) in gamma.to_srgb.iter_mut().enumerate() {
+        let value = linear as f32 / LINEAR_MAX as f32;
+        let value = if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            value.powf(1.0 / 2.4).mul_add(1.055, -0.055)
+        };
+
+        *srgb = (value * 255.0).round() as u8;
+    }
+
+    gamma
+});
+
 impl_vector_overload!(Color r g b);
@@ -2,12 +2,22 @@ mod dcs;
 mod keyboard;
 mod listen;
 mod mouse;
+mod osc;
 mod parser;
+mod query;
+mod record;
+mod report;
+mod resize;
 mod tty;
 
 pub use dcs::*;
 pub use keyboard::*;
 pub use listen::*;
 pub use mouse::*;
+pub use osc::*;
 pub use parser::*;
+pub use query::*;
+pub use record::*;
+pub use report::*;
+pub use resize::*;
 pub use tty::*;
@@ -21,6 +21,11 @@ impl DeviceControl {
         Self::default()
     }
 
+    /// Whether nothing was received after the introducer
+    pub fn is_empty(&self) -> bool {
+        matches!(self.sequence, Sequence::Code)
+    }
+
     pub fn parse(&mut self, key: u8) -> ParseControlFlow {
         use Sequence::*;
 
@@ -1,114 +1,409 @@
-use crate::control_flow;
+use std::borrow::Cow;
 
-use super::{Event, ParseControlFlow};
+use super::Event;
 
-pub struct Keyboard {
-    state: State,
-}
+/// Decoder for keys sent as escape sequences, following the xterm and VT220 grammar:
+/// `CSI [1;modifiers] letter`, `CSI number [;modifiers] ~` and `SS3 letter`,
+/// as well as the kitty keyboard protocol, `CSI code [;modifiers[:event][;text]] u`.
+pub struct Keyboard;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Key {
-    pub char: u8,
+    pub code: KeyCode,
     pub modifiers: KeyModifiers,
+    pub action: KeyAction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCode {
+    /// Key producing text, with the code point it produces without Ctrl, Alt or Meta.
+    ///
+    /// Control characters are reported with the Ctrl modifier: Ctrl+A is `Char('a')`.
+    Char(char),
+    /// Key without text, such as arrows or function keys
+    Named(NamedKey),
+}
+
+/// Whether a key was pressed, held down or released.
+///
+/// Repeats and releases are only reported by terminals
+/// supporting the kitty keyboard protocol.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub enum KeyAction {
+    #[default]
+    Press = 0,
+    Repeat = 1,
+    Release = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NamedKey {
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    /// Center of the keypad, with num lock off
+    Begin,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// Function key, from F1 to F35
+    Function(u8),
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct KeyModifiers {
     pub alt: bool,
+    /// Meta or Super, the Command key on macOS
     pub meta: bool,
     pub shift: bool,
     pub control: bool,
+    pub hyper: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
 }
 
-enum State {
-    Separator,
-    Modifier(u8),
+const FUNCTION_KEYS: [&str; 35] = [
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", "F13", "F14", "F15",
+    "F16", "F17", "F18", "F19", "F20", "F21", "F22", "F23", "F24", "F25", "F26", "F27", "F28",
+    "F29", "F30", "F31", "F32", "F33", "F34", "F35",
+];
+
+impl Keyboard {
+    /// Decode a CSI sequence from its parameters and final byte
+    pub fn csi(params: &[u32], subparams: &[Vec<u32>], key: u8) -> Option<Event> {
+        let param = |index: usize| params.get(index).copied().unwrap_or(0);
+        let modifiers = KeyModifiers::parse(param(1));
+        // Event type following the modifiers, if reported (kitty keyboard protocol)
+        let action = KeyAction::parse(subparams.get(1).and_then(|values| values.first()));
+        let named = |named| Key::named(named, modifiers);
+        let key = match key {
+            b'u' => {
+                // Text of the key, reported after the modifiers
+                let text = char::from_u32(param(2)).filter(|&text| text != '\0');
+
+                Self::kitty(param(0), text, modifiers)?
+            }
+            b'~' => match param(0) {
+                1 | 7 => named(NamedKey::Home),
+                2 => named(NamedKey::Insert),
+                3 => named(NamedKey::Delete),
+                4 | 8 => named(NamedKey::End),
+                5 => named(NamedKey::PageUp),
+                6 => named(NamedKey::PageDown),
+                11..=15 => named(NamedKey::Function(param(0) as u8 - 10)),
+                17..=21 => named(NamedKey::Function(param(0) as u8 - 11)),
+                23..=26 => named(NamedKey::Function(param(0) as u8 - 12)),
+                28 | 29 => named(NamedKey::Function(param(0) as u8 - 13)),
+                31..=34 => named(NamedKey::Function(param(0) as u8 - 14)),
+                // Key with modifiers that can't be encoded otherwise (modifyOtherKeys)
+                27 => Self::kitty(param(2), None, modifiers)?,
+                _ => return None,
+            },
+            // Shift+Tab
+            b'Z' => Key::named(
+                NamedKey::Tab,
+                KeyModifiers {
+                    shift: true,
+                    ..modifiers
+                },
+            ),
+            key => named(Self::letter(key)?),
+        };
+
+        Some(Event::KeyPress {
+            key: Key { action, ..key },
+        })
+    }
+
+    /// Decode a key code of the kitty keyboard protocol, sent as `CSI code u`
+    fn kitty(code: u32, text: Option<char>, modifiers: KeyModifiers) -> Option<Key> {
+        let char = |char: char| Some(Key::char(char, modifiers));
+        let named = |named: NamedKey| Some(Key::named(named, modifiers));
+
+        match code {
+            9 => named(NamedKey::Tab),
+            13 => named(NamedKey::Enter),
+            27 => named(NamedKey::Escape),
+            127 => named(NamedKey::Backspace),
+            57376..=57398 => named(NamedKey::Function((code - 57376 + 13) as u8)),
+            // Keypad
+            57399..=57408 => char((b'0' + (code - 57399) as u8) as char),
+            57409 => char('.'),
+            57410 => char('/'),
+            57411 => char('*'),
+            57412 => char('-'),
+            57413 => char('+'),
+            57414 => named(NamedKey::Enter),
+            57415 => char('='),
+            57416 => char(','),
+            57417 => named(NamedKey::Left),
+            57418 => named(NamedKey::Right),
+            57419 => named(NamedKey::Up),
+            57420 => named(NamedKey::Down),
+            57421 => named(NamedKey::PageUp),
+            57422 => named(NamedKey::PageDown),
+            57423 => named(NamedKey::Home),
+            57424 => named(NamedKey::End),
+            57425 => named(NamedKey::Insert),
+            57426 => named(NamedKey::Delete),
+            57427 => named(NamedKey::Begin),
+            // Lock, media and modifier keys
+            57344..=63743 => {
+                tracing::debug!("ignoring key code: {code}");
+
+                None
+            }
+            // The code is the unshifted key, prefer the text if reported
+            code => match text {
+                Some(text) => char(text),
+                None if modifiers.shift => char(char::from_u32(code)?.to_ascii_uppercase()),
+                None => char(char::from_u32(code)?),
+            },
+        }
+    }
+
+    /// Decode a SS3 sequence from its modifiers, if any, and final byte
+    pub fn ss3(key: u8, modifiers: u8) -> Option<Event> {
+        let modifiers = KeyModifiers::parse(modifiers as u32);
+        let key = match key {
+            // Keypad, in application mode
+            b'M' => Key::named(NamedKey::Enter, modifiers),
+            b'X' => Key::char('=', modifiers),
+            b'j'..=b'y' => Key::char((key - b'j' + b'*') as char, modifiers),
+            key => Key::named(Self::letter(key)?, modifiers),
+        };
+
+        Some(Event::KeyPress { key })
+    }
+
+    /// Named key for the final byte shared by CSI and SS3 sequences
+    fn letter(key: u8) -> Option<NamedKey> {
+        Some(match key {
+            b'A' => NamedKey::Up,
+            b'B' => NamedKey::Down,
+            b'C' => NamedKey::Right,
+            b'D' => NamedKey::Left,
+            b'E' => NamedKey::Begin,
+            b'F' => NamedKey::End,
+            b'H' => NamedKey::Home,
+            b'P'..=b'S' => NamedKey::Function(key - b'P' + 1),
+            _ => return None,
+        })
+    }
 }
 
-impl Default for Keyboard {
-    fn default() -> Self {
+impl Key {
+    pub fn char(char: char, modifiers: KeyModifiers) -> Self {
         Self {
-            state: State::Separator,
+            code: KeyCode::Char(char),
+            modifiers,
+            action: KeyAction::Press,
         }
     }
-}
 
-impl Keyboard {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn named(named: NamedKey, modifiers: KeyModifiers) -> Self {
+        Self {
+            code: KeyCode::Named(named),
+            modifiers,
+            action: KeyAction::Press,
+        }
     }
 
-    pub fn key(key: u8, modifiers: u8) -> Option<Event> {
-        let modifiers = KeyModifiers::parse(modifiers);
-        let char = match key {
-            // Up
-            b'A' => 0x11,
-            // Down
-            b'B' => 0x12,
-            // Right
-            b'C' => 0x13,
-            // Left
-            b'D' => 0x14,
-            _ => return None,
-        };
+    /// Text inserted by the key, if any: keys chorded with Ctrl, Alt or Meta don't insert text
+    pub fn text(&self) -> Option<char> {
+        let KeyModifiers {
+            alt, meta, control, ..
+        } = self.modifiers;
 
-        Some(Event::KeyPress {
-            key: Key { char, modifiers },
+        match self.code {
+            KeyCode::Char(char) if !alt && !meta && !control => Some(char),
+            _ => None,
+        }
+    }
+
+    /// Value of `KeyboardEvent.key` in the browser
+    pub fn dom_key(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self.code {
+            KeyCode::Char(char) => return Cow::Owned(char.to_string()),
+            KeyCode::Named(NamedKey::Function(number)) => {
+                FUNCTION_KEYS[(number as usize).clamp(1, FUNCTION_KEYS.len()) - 1]
+            }
+            KeyCode::Named(NamedKey::Begin) => "Clear",
+            KeyCode::Named(named) => named.dom_code(),
         })
     }
 
-    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
-        self.state = match self.state {
-            State::Separator => match key {
-                b';' => State::Modifier(0),
-                _ => control_flow!(break)?,
-            },
-            State::Modifier(code) => match key {
-                b'0'..=b'9' => State::Modifier(code * 10 + key - b'0'),
-                key => control_flow!(break Self::key(key, code))?,
+    /// Value of `KeyboardEvent.code` in the browser, the physical key on a US layout
+    pub fn dom_code(&self) -> &'static str {
+        match self.code {
+            KeyCode::Named(named) => named.dom_code(),
+            KeyCode::Char(char) => match char.to_ascii_lowercase() {
+                char @ 'a'..='z' => {
+                    const LETTERS: [&str; 26] = [
+                        "KeyA", "KeyB", "KeyC", "KeyD", "KeyE", "KeyF", "KeyG", "KeyH", "KeyI",
+                        "KeyJ", "KeyK", "KeyL", "KeyM", "KeyN", "KeyO", "KeyP", "KeyQ", "KeyR",
+                        "KeyS", "KeyT", "KeyU", "KeyV", "KeyW", "KeyX", "KeyY", "KeyZ",
+                    ];
+
+                    LETTERS[char as usize - 'a' as usize]
+                }
+                '0' | ')' => "Digit0",
+                '1' | '!' => "Digit1",
+                '2' | '@' => "Digit2",
+                '3' | '#' => "Digit3",
+                '4' | '$' => "Digit4",
+                '5' | '%' => "Digit5",
+                '6' | '^' => "Digit6",
+                '7' | '&' => "Digit7",
+                '8' | '*' => "Digit8",
+                '9' | '(' => "Digit9",
+                ' ' => "Space",
+                '-' | '_' => "Minus",
+                '=' | '+' => "Equal",
+                '[' | '{' => "BracketLeft",
+                ']' | '}' => "BracketRight",
+                '\\' | '|' => "Backslash",
+                ';' | ':' => "Semicolon",
+                '\'' | '"' => "Quote",
+                '`' | '~' => "Backquote",
+                ',' | '<' => "Comma",
+                '.' | '>' => "Period",
+                '/' | '?' => "Slash",
+                _ => "",
             },
-        };
+        }
+    }
 
-        control_flow!(continue)
+    /// Byte sent to the browser through the legacy `key_press` callback, if the key has one.
+    ///
+    /// Arrows use the private codes 0x11 to 0x14, which the browser maps back to arrow keys.
+    pub fn legacy_byte(&self) -> Option<u8> {
+        match self.code {
+            KeyCode::Named(NamedKey::Up) => Some(0x11),
+            KeyCode::Named(NamedKey::Down) => Some(0x12),
+            KeyCode::Named(NamedKey::Right) => Some(0x13),
+            KeyCode::Named(NamedKey::Left) => Some(0x14),
+            KeyCode::Named(NamedKey::Enter) => Some(0x0d),
+            KeyCode::Named(NamedKey::Tab) => Some(0x09),
+            KeyCode::Named(NamedKey::Backspace) => Some(0x7f),
+            KeyCode::Named(NamedKey::Escape) => Some(0x1b),
+            KeyCode::Named(_) => None,
+            KeyCode::Char(char) if !char.is_ascii() => None,
+            KeyCode::Char(' ') if self.modifiers.control => Some(0),
+            KeyCode::Char(char @ ('@'..='_' | 'a'..='z')) if self.modifiers.control => {
+                Some(char as u8 & 0x1f)
+            }
+            KeyCode::Char(char) => Some(char as u8),
+        }
     }
 }
 
 impl From<u8> for Key {
-    fn from(char: u8) -> Self {
-        Self {
-            char,
-            modifiers: KeyModifiers::default(),
+    /// Decode a key sent as a single byte, control characters are reported with Ctrl
+    fn from(byte: u8) -> Self {
+        let control = KeyModifiers {
+            control: true,
+            ..Default::default()
+        };
+
+        match byte {
+            0x0d => Self::named(NamedKey::Enter, Default::default()),
+            0x09 => Self::named(NamedKey::Tab, Default::default()),
+            0x08 | 0x7f => Self::named(NamedKey::Backspace, Default::default()),
+            0x1b => Self::named(NamedKey::Escape, Default::default()),
+            0x00 => Self::char(' ', control),
+            0x01..=0x1a => Self::char((b'a' + byte - 1) as char, control),
+            0x1c..=0x1f => Self::char((b'\\' + byte - 0x1c) as char, control),
+            byte => Self::char(byte as char, Default::default()),
+        }
+    }
+}
+
+impl NamedKey {
+    fn dom_code(self) -> &'static str {
+        match self {
+            NamedKey::Enter => "Enter",
+            NamedKey::Tab => "Tab",
+            NamedKey::Backspace => "Backspace",
+            NamedKey::Escape => "Escape",
+            NamedKey::Up => "ArrowUp",
+            NamedKey::Down => "ArrowDown",
+            NamedKey::Right => "ArrowRight",
+            NamedKey::Left => "ArrowLeft",
+            NamedKey::Home => "Home",
+            NamedKey::End => "End",
+            NamedKey::Begin => "Numpad5",
+            NamedKey::Insert => "Insert",
+            NamedKey::Delete => "Delete",
+            NamedKey::PageUp => "PageUp",
+            NamedKey::PageDown => "PageDown",
+            NamedKey::Function(number) => {
+                FUNCTION_KEYS[(number as usize).clamp(1, FUNCTION_KEYS.len()) - 1]
+            }
+        }
+    }
+}
+
+impl KeyAction {
+    /// Parse an event type of the kitty keyboard protocol
+    fn parse(code: Option<&u32>) -> Self {
+        match code {
+            Some(2) => KeyAction::Repeat,
+            Some(3) => KeyAction::Release,
+            _ => KeyAction::Press,
         }
     }
 }
 
 impl KeyModifiers {
-    pub fn parse(key: u8) -> Self {
-        let (alt, meta, shift, control) = (0b1000, 0b0100, 0b0010, 0b0001);
-        let mask = match key {
-            2 => shift,
-            3 => alt,
-            4 => shift | alt,
-            5 => control,
-            6 => shift | control,
-            7 => alt | control,
-            8 => shift | alt | control,
-            9 => meta,
-            10 => meta | shift,
-            11 => meta | alt,
-            12 => meta | alt | shift,
-            13 => meta | control,
-            14 => meta | control | shift,
-            15 => meta | control | alt,
-            16 => meta | control | alt | shift,
-            _ => 0,
-        };
+    const SHIFT: u32 = 0b1;
+    const ALT: u32 = 0b10;
+    const CONTROL: u32 = 0b100;
+    const META: u32 = 0b1000;
+    const HYPER: u32 = 0b1_0000;
+    const CAPS_LOCK: u32 = 0b100_0000;
+    const NUM_LOCK: u32 = 0b1000_0000;
+
+    /// Parse a modifiers parameter, one plus a bit mask of the active modifiers
+    pub fn parse(code: u32) -> Self {
+        let mask = code.saturating_sub(1);
+        let bit = |bit: u32| mask & bit != 0;
 
         KeyModifiers {
-            alt: alt & mask != 0,
-            meta: meta & mask != 0,
-            shift: shift & mask != 0,
-            control: control & mask != 0,
+            shift: bit(Self::SHIFT),
+            alt: bit(Self::ALT),
+            control: bit(Self::CONTROL),
+            // Super in the kitty keyboard protocol, Meta in xterm
+            meta: bit(Self::META) || bit(0b10_0000),
+            hyper: bit(Self::HYPER),
+            caps_lock: bit(Self::CAPS_LOCK),
+            num_lock: bit(Self::NUM_LOCK),
         }
     }
+
+    /// Bit mask of the active modifiers, using the bits of the modifiers parameter:
+    /// shift 1, alt 2, control 4, meta 8, hyper 16, caps lock 64 and num lock 128.
+    pub fn mask(&self) -> u32 {
+        [
+            (self.shift, Self::SHIFT),
+            (self.alt, Self::ALT),
+            (self.control, Self::CONTROL),
+            (self.meta, Self::META),
+            (self.hyper, Self::HYPER),
+            (self.caps_lock, Self::CAPS_LOCK),
+            (self.num_lock, Self::NUM_LOCK),
+        ]
+        .into_iter()
+        .filter(|&(active, _)| active)
+        .fold(0, |mask, (_, bit)| mask | bit)
+    }
 }
@@ -1,28 +1,49 @@
+use std::convert::Infallible;
 use std::io::{self, Read};
+use std::time::Duration;
 
 use crate::input::*;
 
+/// Delay after which an escape not followed by a sequence is a key press
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
 /// Listen for input events in stdin.
-/// This will block, so it should run from a dedicated thread.
-pub fn listen<F>(mut callback: F) -> io::Result<()>
+/// This blocks until reading fails, so it should run from a dedicated thread.
+///
+/// `pixel_mouse` tells whether the terminal reports mouse positions in pixels.
+pub fn listen<F>(pixel_mouse: bool, mut callback: F) -> io::Result<Infallible>
 where
     F: FnMut(Vec<Event>),
 {
-    let mut buf = [0u8; 1024];
+    // As large as the stdin buffer, so that reads bypass it
+    // and polling stdin accounts for all the pending input
+    let mut buf = vec![0u8; 8 * 1024];
     let mut stdin = io::stdin();
     let mut parser = Parser::new();
 
     loop {
-        // Wait for some input
-        let size = stdin.read(&mut buf)?;
-        let read = parser.parse(&buf[0..size]);
-        let mut scroll = 0;
+        // Sequences split across reads are completed by the next one,
+        // unless an escape is pressed alone
+        let read = if parser.ambiguous() && !poll(ESCAPE_TIMEOUT)? {
+            parser.timeout()
+        } else {
+            // Wait for some input
+            let size = stdin.read(&mut buf)?;
+
+            // Keep the cell size up to date, it changes with the terminal font size
+            if pixel_mouse && let Some(cell) = cell_pixels() {
+                parser.set_cell_pixels(Some(cell))
+            }
+
+            parser.parse(&buf[0..size])
+        };
+        let (mut scroll, mut horizontal) = (0, 0);
         let mut events = Vec::with_capacity(read.len());
 
         for event in read {
             match event {
-                Event::Exit => return Ok(()),
                 Event::Scroll { delta } => scroll += delta,
+                Event::HorizontalScroll { delta } => horizontal += delta,
                 event => events.push(event),
             }
         }
@@ -30,7 +51,30 @@ where
         if scroll != 0 {
             events.push(Event::Scroll { delta: scroll })
         }
+        if horizontal != 0 {
+            events.push(Event::HorizontalScroll { delta: horizontal })
+        }
 
         callback(events)
     }
 }
+
+/// Wait for input on stdin, returns `false` if there's none after `timeout`
+fn poll(timeout: Duration) -> io::Result<bool> {
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        match unsafe { libc::poll(&mut fds, 1, timeout.as_millis() as libc::c_int) } {
+            -1 => match io::Error::last_os_error() {
+                // Interrupted by a signal, such as a resize
+                error if error.kind() == io::ErrorKind::Interrupted => continue,
+                error => return Err(error),
+            },
+            ready => return Ok(ready > 0),
+        }
+    }
+}
@@ -1,11 +1,24 @@
 use std::ops::BitAnd;
 
-use crate::control_flow;
-
-use super::{Event, ParseControlFlow};
+use crate::{control_flow, gfx::Size};
+
+use super::{Event, KeyModifiers, ParseControlFlow};
+
+/// Mouse button, numbered like `MouseEvent.button` in the browser
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub enum MouseButton {
+    Left = 0,
+    Middle = 1,
+    Right = 2,
+    Back = 3,
+    Forward = 4,
+}
 
 #[derive(Default, Clone, Debug)]
 pub struct Mouse {
+    /// Size of a cell in pixels, if positions are reported in pixels
+    cell_pixels: Option<Size<f32>>,
     buf: Vec<u8>,
     btn: Option<u32>,
     col: Option<u32>,
@@ -13,8 +26,11 @@ pub struct Mouse {
 }
 
 impl Mouse {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(cell_pixels: Option<Size<f32>>) -> Self {
+        Self {
+            cell_pixels,
+            ..Self::default()
+        }
     }
 
     pub fn parse(&mut self, key: u8) -> ParseControlFlow {
@@ -54,31 +70,80 @@ impl Mouse {
             (self.btn?, self.col?, self.row?)
         };
 
-        Some({
-            if Mask::ScrollDown & btn {
-                Event::Scroll { delta: -1 }
-            } else if Mask::ScrollUp & btn {
-                Event::Scroll { delta: 1 }
-            } else {
-                let col = col as usize - 1;
-                let row = row as usize - 1;
-
-                if key == b'm' {
-                    Event::MouseUp { row, col }
-                } else if Mask::MouseMove & btn {
-                    Event::MouseMove { row, col }
-                } else {
-                    Event::MouseDown { row, col }
-                }
+        if Mask::Wheel & btn && !(Mask::Extra & btn) {
+            // Up, down, left and right, Shift turns vertical scrolling horizontal
+            return Some(match (btn & 0b11, Mask::Shift & btn) {
+                (0, false) => Event::Scroll { delta: 1 },
+                (1, false) => Event::Scroll { delta: -1 },
+                (0, true) | (2, _) => Event::HorizontalScroll { delta: 1 },
+                _ => Event::HorizontalScroll { delta: -1 },
+            });
+        }
+
+        // Reported positions start at 1
+        let (col, row) = match self.cell_pixels {
+            Some(cell) => (
+                col.saturating_sub(1) as f32 / cell.width,
+                row.saturating_sub(1) as f32 / cell.height,
+            ),
+            None => (
+                col.saturating_sub(1) as f32 + 0.5,
+                row.saturating_sub(1) as f32 + 0.5,
+            ),
+        };
+        let modifiers = KeyModifiers {
+            shift: Mask::Shift & btn,
+            alt: Mask::Alt & btn,
+            control: Mask::Control & btn,
+            ..Default::default()
+        };
+        let button = match (Mask::Extra & btn, btn & 0b11) {
+            (false, 0) => Some(MouseButton::Left),
+            (false, 1) => Some(MouseButton::Middle),
+            (false, 2) => Some(MouseButton::Right),
+            (true, 0) => Some(MouseButton::Back),
+            (true, 1) => Some(MouseButton::Forward),
+            _ => None,
+        };
+
+        Some(if Mask::MouseMove & btn {
+            // Button held down while moving, if any
+            Event::MouseMove {
+                row,
+                col,
+                button,
+                modifiers,
+            }
+        } else {
+            let button = button?;
+
+            match key {
+                b'm' => Event::MouseUp {
+                    row,
+                    col,
+                    button,
+                    modifiers,
+                },
+                _ => Event::MouseDown {
+                    row,
+                    col,
+                    button,
+                    modifiers,
+                },
             }
         })
     }
 }
 
 enum Mask {
+    Shift = 0x04,
+    Alt = 0x08,
+    Control = 0x10,
     MouseMove = 0x20,
-    ScrollUp = 0x40,
-    ScrollDown = 0x41,
+    /// Wheel buttons 4 to 7
+    Wheel = 0x40,
+    /// Buttons 8 to 11
+    Extra = 0x80,
 }
 
 impl BitAnd<u32> for Mask {
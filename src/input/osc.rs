@@ -0,0 +1,115 @@
+use crate::{
+    control_flow,
+    gfx::Color,
+    input::{Event, ParseControlFlow, TerminalEvent},
+    utils::base64,
+};
+
+/// Operating system command (OSC) reply, terminated by BEL or ST
+#[derive(Default, Clone)]
+pub struct OperatingSystem {
+    data: Vec<u8>,
+}
+
+impl OperatingSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing was received after the introducer
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        match key {
+            // BEL
+            0x07 => (),
+            // ST, sent as ESC \
+            b'\\' if self.data.last() == Some(&0x1b) => {
+                self.data.pop();
+            }
+            key => {
+                self.data.push(key);
+
+                return control_flow!(continue);
+            }
+        }
+
+        control_flow!(break self.event())
+    }
+
+    fn event(&self) -> Option<Event> {
+        let data = String::from_utf8_lossy(&self.data);
+        let (code, data) = data.split_once(';')?;
+
+        match code {
+            // Palette color: index;color
+            "4" => {
+                let (index, color) = data.split_once(';')?;
+
+                Some(Event::Terminal(TerminalEvent::PaletteColor {
+                    index: index.parse().ok()?,
+                    color: parse_color(color)?,
+                }))
+            }
+            "10" => Some(Event::Terminal(TerminalEvent::ForegroundColor(
+                parse_color(data)?,
+            ))),
+            "11" => Some(Event::Terminal(TerminalEvent::BackgroundColor(
+                parse_color(data)?,
+            ))),
+            // Clipboard content: selection;base64
+            "52" => {
+                let (_, content) = data.split_once(';')?;
+                let content = base64::decode(content)?;
+
+                Some(Event::Terminal(TerminalEvent::Clipboard(
+                    String::from_utf8_lossy(&content).into_owned(),
+                )))
+            }
+            _ => {
+                tracing::debug!("ignoring OSC reply: {code};{data}");
+
+                None
+            }
+        }
+    }
+}
+
+/// Parse a color reported as `rgb:R/G/B`, with 1 to 4 hex digits per channel,
+/// or as `#RRGGBB`.
+fn parse_color(spec: &str) -> Option<Color> {
+    let channel = |hex: &str| {
+        if !(1..=4).contains(&hex.len()) {
+            return None;
+        }
+
+        let max = (1u32 << (hex.len() * 4)) - 1;
+        let value = u32::from_str_radix(hex, 16).ok()?;
+
+        Some((value * 255 / max) as u8)
+    };
+
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let mut channels = rgb.split('/').map(channel);
+
+        return match (
+            channels.next(),
+            channels.next(),
+            channels.next(),
+            channels.next(),
+        ) {
+            (Some(r), Some(g), Some(b), None) => Some(Color::new(r?, g?, b?)),
+            _ => None,
+        };
+    }
+
+    let hex = spec.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+
+    Some(Color::new(
+        channel(hex.get(0..2)?)?,
+        channel(hex.get(2..4)?)?,
+        channel(hex.get(4..6)?)?,
+    ))
+}
@@ -1,39 +1,115 @@
 use std::ops::ControlFlow;
 
-use crate::input::*;
+use crate::{
+    gfx::{Color, Size},
+    input::*,
+};
 
 #[derive(Default)]
 pub struct Parser {
     events: Vec<Event>,
     sequence: Sequence,
+    /// Size of a cell in pixels, if mouse positions are reported in pixels
+    cell_pixels: Option<Size<f32>>,
 }
 
 #[derive(Default)]
 enum Sequence {
     #[default]
     Char,
+    /// Multi-byte UTF-8 character, with the number of bytes received,
+    /// and whether it was sent after an escape for Alt
+    Text([u8; 4], usize, bool),
     Escape,
     Control,
+    /// Single shift (SS3) key, with its modifiers if any
+    SingleShift(u8),
     Mouse(Mouse),
-    Keyboard(Keyboard),
+    Report(Report),
     DeviceControl(DeviceControl),
+    OperatingSystem(OperatingSystem),
+    /// Bracketed paste, with the text received so far
+    Paste(Vec<u8>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum TerminalEvent {
     Name(String),
     TrueColorSupported,
+    /// Size of the text area in pixels (XTWINOPS 14)
+    TextAreaPixels(Size),
+    /// Size of a cell in pixels (XTWINOPS 16)
+    CellPixels(Size),
+    /// Size of the text area in cells (XTWINOPS 18)
+    TextAreaCells(Size),
+    /// Reply to a primary device attributes request
+    DeviceAttributes,
+    /// Keyboard enhancement flags currently enabled (kitty keyboard protocol)
+    KeyboardFlags(u32),
+    /// State of a private mode (DECRPM): 0 if not recognized, 1 or 3 if set, 2 or 4 if reset
+    Mode {
+        mode: u32,
+        state: u32,
+    },
+    /// Position of the cursor (CPR), starting at 1
+    CursorPosition {
+        row: u32,
+        col: u32,
+    },
+    /// Clipboard content (OSC 52), if the terminal allows reading it
+    Clipboard(String),
+    /// Default text color (OSC 10)
+    ForegroundColor(Color),
+    /// Default background color (OSC 11)
+    BackgroundColor(Color),
+    /// Color of the palette at `index` (OSC 4)
+    PaletteColor {
+        index: u8,
+        color: Color,
+    },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Event {
-    KeyPress { key: Key },
-    MouseUp { row: usize, col: usize },
-    MouseDown { row: usize, col: usize },
-    MouseMove { row: usize, col: usize },
-    Scroll { delta: isize },
+    KeyPress {
+        key: Key,
+    },
+    /// Text pasted in the terminal, with newlines normalized to `\n`
+    Paste(String),
+    /// Mouse button released, positions are in cells, with a fractional part
+    /// for the position inside the cell: the cell center if reported in cells.
+    MouseUp {
+        row: f32,
+        col: f32,
+        button: MouseButton,
+        modifiers: KeyModifiers,
+    },
+    MouseDown {
+        row: f32,
+        col: f32,
+        button: MouseButton,
+        modifiers: KeyModifiers,
+    },
+    /// Mouse moved, dragging if a button is held down
+    MouseMove {
+        row: f32,
+        col: f32,
+        button: Option<MouseButton>,
+        modifiers: KeyModifiers,
+    },
+    /// Vertical wheel, positive values scroll up
+    Scroll {
+        delta: isize,
+    },
+    /// Horizontal wheel, or vertical wheel with Shift, positive values scroll left
+    HorizontalScroll {
+        delta: isize,
+    },
+    /// Terminal window focused or unfocused
+    Focus {
+        focused: bool,
+    },
     Terminal(TerminalEvent),
-    Exit,
 }
 
 pub type ParseControlFlow = ControlFlow<Option<Event>, Option<Event>>;
@@ -43,6 +119,56 @@ impl Parser {
         Self::default()
     }
 
+    /// Set the size of a cell in pixels when mouse positions are reported in pixels
+    /// (SGR-Pixels), or `None` when they are reported in cells.
+    pub fn set_cell_pixels(&mut self, cell: Option<Size<f32>>) {
+        self.cell_pixels = cell
+    }
+
+    /// Whether the input ends with an escape that could be a key press
+    /// or the start of a sequence, see [`Parser::timeout`].
+    pub fn ambiguous(&self) -> bool {
+        match &self.sequence {
+            Sequence::Escape | Sequence::Control | Sequence::SingleShift(0) => true,
+            Sequence::DeviceControl(dcs) => dcs.is_empty(),
+            Sequence::OperatingSystem(osc) => osc.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Report an ambiguous escape as key presses, once no sequence followed it in time.
+    ///
+    /// The escape key is pressed alone, or with Alt for the introducer of a sequence.
+    pub fn timeout(&mut self) -> Vec<Event> {
+        let key = match self.sequence {
+            Sequence::Escape => Event::KeyPress { key: 0x1b.into() },
+            Sequence::Control => Self::alt(b'['),
+            Sequence::SingleShift(0) => Self::alt(b'O'),
+            Sequence::DeviceControl(ref dcs) if dcs.is_empty() => Self::alt(b'P'),
+            Sequence::OperatingSystem(ref osc) if osc.is_empty() => Self::alt(b']'),
+            _ => return Vec::new(),
+        };
+
+        self.sequence = Sequence::Char;
+
+        vec![key]
+    }
+
+    /// Key sent as a byte after an escape, pressed with Alt
+    fn alt(byte: u8) -> Event {
+        let key = Key::from(byte);
+
+        Event::KeyPress {
+            key: Key {
+                modifiers: KeyModifiers {
+                    alt: true,
+                    ..key.modifiers
+                },
+                ..key
+            },
+        }
+    }
+
     pub fn parse(&mut self, input: &[u8]) -> Vec<Event> {
         let mut sequence = std::mem::take(&mut self.sequence);
 
@@ -77,26 +203,90 @@ impl Parser {
             sequence = match sequence {
                 Sequence::Char => match key {
                     0x1b => Sequence::Escape,
-                    0x03 => emit!(Event::Exit),
-                    key => emit!(Event::KeyPress { key: key.into() }),
+                    0x00..=0x7f => emit!(Event::KeyPress { key: key.into() }),
+                    0xc2..=0xf4 => Sequence::Text([key, 0, 0, 0], 1, false),
+                    key => {
+                        tracing::debug!("invalid UTF-8 input: {key:#x}");
+
+                        Sequence::Char
+                    }
                 },
+                Sequence::Text(mut bytes, len, alt) => {
+                    bytes[len] = key;
+
+                    match std::str::from_utf8(&bytes[..=len]) {
+                        Ok(text) => emit!(text.chars().next().map(|char| Event::KeyPress {
+                            key: Key::char(
+                                char,
+                                KeyModifiers {
+                                    alt,
+                                    ..Default::default()
+                                }
+                            )
+                        })),
+                        // Wait for the rest of the character
+                        Err(error) if error.error_len().is_none() => {
+                            Sequence::Text(bytes, len + 1, alt)
+                        }
+                        Err(_) => {
+                            tracing::debug!("invalid UTF-8 input: {:?}", &bytes[..=len]);
+
+                            Sequence::Char
+                        }
+                    }
+                }
                 Sequence::Escape => match key {
                     b'[' => Sequence::Control,
                     b'P' => Sequence::DeviceControl(DeviceControl::new()),
+                    b']' => Sequence::OperatingSystem(OperatingSystem::new()),
+                    b'O' => Sequence::SingleShift(0),
                     0x1b => emit!(Event::KeyPress { key: 0x1b.into() }; continue),
-                    key => {
-                        emit!(Event::KeyPress { key: 0x1b.into() });
-                        emit!(Event::KeyPress { key: key.into() })
+                    // Keys sent after an escape are pressed with Alt
+                    0xc2..=0xf4 => Sequence::Text([key, 0, 0, 0], 1, true),
+                    0x80.. => {
+                        tracing::debug!("invalid UTF-8 input: {key:#x}");
+
+                        emit!(Event::KeyPress { key: 0x1b.into() })
                     }
+                    key => emit!(Self::alt(key)),
                 },
                 Sequence::Control => match key {
-                    b'<' => Sequence::Mouse(Mouse::new()),
-                    b'1' => Sequence::Keyboard(Keyboard::new()),
-                    key => emit!(Keyboard::key(key, 0)),
+                    b'<' => Sequence::Mouse(Mouse::new(self.cell_pixels)),
+                    b'0'..=b'9' | b';' | b'?' => Sequence::Report(Report::new(key)),
+                    b'I' => emit!(Event::Focus { focused: true }),
+                    b'O' => emit!(Event::Focus { focused: false }),
+                    key => emit!(Keyboard::csi(&[], &[], key)),
+                },
+                Sequence::SingleShift(modifiers) => match key {
+                    b'0'..=b'9' => Sequence::SingleShift(
+                        modifiers.saturating_mul(10).saturating_add(key - b'0'),
+                    ),
+                    key => emit!(Keyboard::ss3(key, modifiers)),
                 },
                 Sequence::Mouse(ref mut mouse) => parse!(mouse, key),
-                Sequence::Keyboard(ref mut keyboard) => parse!(keyboard, key),
+                Sequence::Report(ref report) if report.starts_paste(key) => {
+                    Sequence::Paste(Vec::new())
+                }
+                Sequence::Report(ref mut report) => parse!(report, key),
+                Sequence::Paste(ref mut text) => {
+                    const END: &[u8] = b"\x1b[201~";
+
+                    text.push(key);
+
+                    if !text.ends_with(END) {
+                        continue;
+                    }
+
+                    text.truncate(text.len() - END.len());
+
+                    let text = String::from_utf8_lossy(text)
+                        .replace("\r\n", "\n")
+                        .replace('\r', "\n");
+
+                    emit!(Event::Paste(text))
+                }
                 Sequence::DeviceControl(ref mut dcs) => parse!(dcs, key),
+                Sequence::OperatingSystem(ref mut osc) => parse!(osc, key),
             }
         }
 
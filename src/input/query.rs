@@ -0,0 +1,55 @@
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::input::*;
+
+/// Send queries to the terminal and collect its replies.
+///
+/// A primary device attributes request is appended to the queries. Terminals
+/// answer it even if they ignore the other queries, so we can stop reading as
+/// soon as it's received instead of waiting for the `timeout`.
+///
+/// This reads from stdin, so it should not run while [`listen`] is running.
+/// Input that is not a reply is dropped.
+pub fn query_terminal(queries: &str, timeout: Duration) -> io::Result<Vec<TerminalEvent>> {
+    let mut stdout = io::stdout();
+
+    write!(stdout, "{queries}\x1b[c")?;
+    stdout.flush()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1024];
+    let mut stdin = io::stdin();
+    let mut parser = Parser::new();
+    let mut replies = Vec::new();
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            tracing::debug!("terminal query timed out, replies: {replies:?}");
+
+            return Ok(replies);
+        }
+
+        // Wait for some input without blocking past the deadline
+        if unsafe { libc::poll(&mut fds, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+            continue;
+        }
+
+        let size = stdin.read(&mut buf)?;
+
+        for event in parser.parse(&buf[0..size]) {
+            match event {
+                Event::Terminal(TerminalEvent::DeviceAttributes) => return Ok(replies),
+                Event::Terminal(reply) => replies.push(reply),
+                event => tracing::debug!("dropping input during terminal query: {event:?}"),
+            }
+        }
+    }
+}
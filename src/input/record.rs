@@ -0,0 +1,428 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::gfx::{Color, Size};
+
+use super::{Event, Key, KeyAction, KeyCode, KeyModifiers, MouseButton, NamedKey, TerminalEvent};
+
+/// First line of a recording, with the version of the format
+const HEADER: &str = "# carbonyl input 1";
+
+const NAMED_KEYS: [(&str, NamedKey); 15] = [
+    ("enter", NamedKey::Enter),
+    ("tab", NamedKey::Tab),
+    ("backspace", NamedKey::Backspace),
+    ("escape", NamedKey::Escape),
+    ("up", NamedKey::Up),
+    ("down", NamedKey::Down),
+    ("right", NamedKey::Right),
+    ("left", NamedKey::Left),
+    ("home", NamedKey::Home),
+    ("end", NamedKey::End),
+    ("begin", NamedKey::Begin),
+    ("insert", NamedKey::Insert),
+    ("delete", NamedKey::Delete),
+    ("page-up", NamedKey::PageUp),
+    ("page-down", NamedKey::PageDown),
+];
+
+const MOUSE_BUTTONS: [(&str, MouseButton); 5] = [
+    ("left", MouseButton::Left),
+    ("middle", MouseButton::Middle),
+    ("right", MouseButton::Right),
+    ("back", MouseButton::Back),
+    ("forward", MouseButton::Forward),
+];
+
+/// Write input events to a file, one per line, prefixed with the number
+/// of milliseconds since the recording started:
+///
+/// ```text
+/// # carbonyl input 1
+/// 0 terminal true-color
+/// 1520 key press 4 U+006C
+/// 2210 paste https://example.com/%20a
+/// 2215 mouse-down 12.5 3.5 left 0
+/// ```
+///
+/// Events read together share the same timestamp, and are replayed together.
+pub struct Recorder {
+    output: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Start recording to a new file at `path`
+    pub fn create(path: &Path) -> io::Result<Recorder> {
+        Self::with_output(Box::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn with_output(mut output: Box<dyn Write + Send>) -> io::Result<Recorder> {
+        writeln!(output, "{HEADER}")?;
+
+        Ok(Recorder {
+            output,
+            start: Instant::now(),
+        })
+    }
+
+    /// Record a batch of events, written right away
+    pub fn record(&mut self, events: &[Event]) -> io::Result<()> {
+        let time = self.start.elapsed().as_millis();
+
+        for event in events {
+            writeln!(self.output, "{time} {event}")?
+        }
+
+        self.output.flush()
+    }
+}
+
+/// Replay events recorded with a [`Recorder`], in batches of events sharing a timestamp.
+///
+/// Batches are delayed like they were recorded, `speed` times faster,
+/// or not delayed at all if `speed` is 0.
+pub fn replay<F>(input: impl BufRead, speed: f32, mut callback: F) -> io::Result<()>
+where
+    F: FnMut(Vec<Event>),
+{
+    let start = Instant::now();
+    let mut batch: Option<(u64, Vec<Event>)> = None;
+    let mut send = |(time, events): (u64, Vec<Event>)| {
+        if speed > 0.0 {
+            let deadline = start + Duration::from_millis(time).div_f32(speed);
+
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        callback(events)
+    };
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {error}", index + 1),
+            )
+        };
+        let (time, event) = line
+            .split_once(' ')
+            .ok_or_else(|| invalid(format!("expected a timestamp and an event: {line:?}")))?;
+        let time = time
+            .parse::<u64>()
+            .map_err(|error| invalid(format!("invalid timestamp {time:?}: {error}")))?;
+        let event = event.parse::<Event>().map_err(invalid)?;
+
+        match batch {
+            Some((batch_time, ref mut events)) if batch_time == time => events.push(event),
+            _ => {
+                if let Some(previous) = batch.replace((time, vec![event])) {
+                    send(previous)
+                }
+            }
+        }
+    }
+
+    if let Some(batch) = batch {
+        send(batch)
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |button: &MouseButton| {
+            MOUSE_BUTTONS
+                .iter()
+                .find(|(_, value)| value == button)
+                .map_or("-", |&(name, _)| name)
+        };
+
+        match self {
+            Event::KeyPress { key } => {
+                let action = match key.action {
+                    KeyAction::Press => "press",
+                    KeyAction::Repeat => "repeat",
+                    KeyAction::Release => "release",
+                };
+
+                write!(f, "key {action} {} ", key.modifiers.mask())?;
+
+                match key.code {
+                    KeyCode::Char(char) => write!(f, "U+{:04X}", char as u32),
+                    KeyCode::Named(NamedKey::Function(number)) => write!(f, "f{number}"),
+                    KeyCode::Named(named) => {
+                        let name = NAMED_KEYS.iter().find(|(_, value)| *value == named);
+
+                        f.write_str(name.map_or("-", |&(name, _)| name))
+                    }
+                }
+            }
+            Event::Paste(text) => write!(f, "paste {}", escape(text)),
+            Event::MouseUp {
+                row,
+                col,
+                button,
+                modifiers,
+            } => write!(
+                f,
+                "mouse-up {col} {row} {} {}",
+                name(button),
+                modifiers.mask()
+            ),
+            Event::MouseDown {
+                row,
+                col,
+                button,
+                modifiers,
+            } => write!(
+                f,
+                "mouse-down {col} {row} {} {}",
+                name(button),
+                modifiers.mask()
+            ),
+            Event::MouseMove {
+                row,
+                col,
+                button: held,
+                modifiers,
+            } => write!(
+                f,
+                "mouse-move {col} {row} {} {}",
+                held.as_ref().map_or("-", name),
+                modifiers.mask()
+            ),
+            Event::Scroll { delta } => write!(f, "scroll {delta}"),
+            Event::HorizontalScroll { delta } => write!(f, "horizontal-scroll {delta}"),
+            Event::Focus { focused: true } => write!(f, "focus in"),
+            Event::Focus { focused: false } => write!(f, "focus out"),
+            Event::Terminal(event) => {
+                f.write_str("terminal ")?;
+
+                match event {
+                    TerminalEvent::Name(name) => write!(f, "name {}", escape(name)),
+                    TerminalEvent::TrueColorSupported => write!(f, "true-color"),
+                    TerminalEvent::TextAreaPixels(size) => {
+                        write!(f, "text-area-pixels {} {}", size.width, size.height)
+                    }
+                    TerminalEvent::CellPixels(size) => {
+                        write!(f, "cell-pixels {} {}", size.width, size.height)
+                    }
+                    TerminalEvent::TextAreaCells(size) => {
+                        write!(f, "text-area-cells {} {}", size.width, size.height)
+                    }
+                    TerminalEvent::DeviceAttributes => write!(f, "device-attributes"),
+                    TerminalEvent::KeyboardFlags(flags) => write!(f, "keyboard-flags {flags}"),
+                    TerminalEvent::Mode { mode, state } => write!(f, "mode {mode} {state}"),
+                    TerminalEvent::CursorPosition { row, col } => {
+                        write!(f, "cursor-position {row} {col}")
+                    }
+                    TerminalEvent::Clipboard(text) => write!(f, "clipboard {}", escape(text)),
+                    TerminalEvent::ForegroundColor(color) => {
+                        write!(f, "foreground #{:06x}", color.pack())
+                    }
+                    TerminalEvent::BackgroundColor(color) => {
+                        write!(f, "background #{:06x}", color.pack())
+                    }
+                    TerminalEvent::PaletteColor { index, color } => {
+                        write!(f, "palette {index} #{:06x}", color.pack())
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = String;
+
+    /// Parse an event written by [`Recorder`], without its timestamp
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut fields = line.split(' ');
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| format!("missing field: {line:?}"))
+        };
+        let kind = next()?;
+
+        macro_rules! parse {
+            () => {{
+                let field = next()?;
+
+                field
+                    .parse()
+                    .map_err(|error| format!("invalid field {field:?}: {error}"))?
+            }};
+        }
+
+        let modifiers = |mask: u32| KeyModifiers::parse(mask + 1);
+        let button = |name: &str| {
+            MOUSE_BUTTONS
+                .iter()
+                .find(|&&(value, _)| value == name)
+                .map(|&(_, button)| button)
+        };
+        let color = |hex: &str| {
+            hex.strip_prefix('#')
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .map(Color::unpack)
+                .ok_or_else(|| format!("invalid color: {hex}"))
+        };
+        let required = |name: &str| button(name).ok_or_else(|| format!("invalid button: {name}"));
+
+        let event = match kind {
+            "key" => {
+                let action = match next()? {
+                    "press" => KeyAction::Press,
+                    "repeat" => KeyAction::Repeat,
+                    "release" => KeyAction::Release,
+                    action => return Err(format!("invalid key action: {action}")),
+                };
+                let modifiers = modifiers(parse!());
+                let code = match next()? {
+                    code if code.starts_with("U+") => {
+                        let char = u32::from_str_radix(&code[2..], 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid character: {code}"))?;
+
+                        KeyCode::Char(char)
+                    }
+                    code => KeyCode::Named(
+                        match code.strip_prefix('f').map(str::parse::<u8>) {
+                            Some(Ok(number)) => Some(NamedKey::Function(number)),
+                            _ => NAMED_KEYS
+                                .iter()
+                                .find(|&&(name, _)| name == code)
+                                .map(|&(_, named)| named),
+                        }
+                        .ok_or_else(|| format!("invalid key: {code}"))?,
+                    ),
+                };
+
+                Event::KeyPress {
+                    key: Key {
+                        code,
+                        modifiers,
+                        action,
+                    },
+                }
+            }
+            "paste" => Event::Paste(unescape(next()?)?),
+            "mouse-up" => Event::MouseUp {
+                col: parse!(),
+                row: parse!(),
+                button: required(next()?)?,
+                modifiers: modifiers(parse!()),
+            },
+            "mouse-down" => Event::MouseDown {
+                col: parse!(),
+                row: parse!(),
+                button: required(next()?)?,
+                modifiers: modifiers(parse!()),
+            },
+            "mouse-move" => Event::MouseMove {
+                col: parse!(),
+                row: parse!(),
+                button: match next()? {
+                    "-" => None,
+                    name => Some(required(name)?),
+                },
+                modifiers: modifiers(parse!()),
+            },
+            "scroll" => Event::Scroll { delta: parse!() },
+            "horizontal-scroll" => Event::HorizontalScroll { delta: parse!() },
+            "focus" => Event::Focus {
+                focused: match next()? {
+                    "in" => true,
+                    "out" => false,
+                    focus => return Err(format!("invalid focus: {focus}")),
+                },
+            },
+            "terminal" => Event::Terminal(match next()? {
+                "name" => TerminalEvent::Name(unescape(next()?)?),
+                "true-color" => TerminalEvent::TrueColorSupported,
+                "text-area-pixels" => TerminalEvent::TextAreaPixels(Size::new(parse!(), parse!())),
+                "cell-pixels" => TerminalEvent::CellPixels(Size::new(parse!(), parse!())),
+                "text-area-cells" => TerminalEvent::TextAreaCells(Size::new(parse!(), parse!())),
+                "device-attributes" => TerminalEvent::DeviceAttributes,
+                "keyboard-flags" => TerminalEvent::KeyboardFlags(parse!()),
+                "mode" => TerminalEvent::Mode {
+                    mode: parse!(),
+                    state: parse!(),
+                },
+                "cursor-position" => TerminalEvent::CursorPosition {
+                    row: parse!(),
+                    col: parse!(),
+                },
+                "clipboard" => TerminalEvent::Clipboard(unescape(next()?)?),
+                "foreground" => TerminalEvent::ForegroundColor(color(next()?)?),
+                "background" => TerminalEvent::BackgroundColor(color(next()?)?),
+                "palette" => TerminalEvent::PaletteColor {
+                    index: parse!(),
+                    color: color(next()?)?,
+                },
+                event => return Err(format!("invalid terminal event: {event}")),
+            }),
+            kind => return Err(format!("invalid event: {kind}")),
+        };
+
+        match fields.next() {
+            None => Ok(event),
+            Some(_) => Err(format!("unexpected fields: {line:?}")),
+        }
+    }
+}
+
+/// Percent-encode spaces, `%`, and bytes outside of printable ASCII
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for byte in text.bytes() {
+        match byte {
+            b'!'..=b'~' if byte != b'%' => escaped.push(byte as char),
+            byte => escaped.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+
+    while let Some(byte) = input.next() {
+        match byte {
+            b'%' => {
+                let hex = [input.next(), input.next()];
+                let byte = match hex {
+                    [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                    _ => None,
+                };
+
+                bytes.push(byte.ok_or_else(|| format!("invalid escape in {text:?}"))?)
+            }
+            byte => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|error| format!("invalid UTF-8 in {text:?}: {error}"))
+}
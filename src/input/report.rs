@@ -0,0 +1,102 @@
+use crate::{control_flow, gfx::Size};
+
+use super::{Event, Keyboard, ParseControlFlow, TerminalEvent};
+
+/// Parser for CSI sequences with parameters: replies to terminal queries,
+/// such as window reports (XTWINOPS) or device attributes, and keys.
+#[derive(Default, Clone, Debug)]
+pub struct Report {
+    private: bool,
+    /// Intermediate byte preceding the final byte, such as `$`
+    intermediate: Option<u8>,
+    params: Vec<u32>,
+    /// Colon separated values following each parameter
+    subparams: Vec<Vec<u32>>,
+    /// Values of the current parameter, before the last colon
+    values: Vec<u32>,
+    current: Option<u32>,
+}
+
+impl Report {
+    /// Create a parser from the first byte following the CSI
+    pub fn new(key: u8) -> Self {
+        let mut report = Self::default();
+
+        match key {
+            b'?' => report.private = true,
+            key => _ = report.parse(key),
+        }
+
+        report
+    }
+
+    pub fn parse(&mut self, key: u8) -> ParseControlFlow {
+        match key {
+            b'0'..=b'9' => {
+                let digit = (key - b'0') as u32;
+
+                self.current = Some(self.current.unwrap_or(0).saturating_mul(10) + digit);
+
+                control_flow!(continue)
+            }
+            b':' => {
+                self.values.push(self.current.take().unwrap_or(0));
+
+                control_flow!(continue)
+            }
+            b';' => control_flow!(self.push(); continue),
+            0x20..=0x2f => {
+                self.intermediate = Some(key);
+
+                control_flow!(continue)
+            }
+            // Final byte
+            0x40..=0x7e => control_flow!(break self.event(key)),
+            _ => control_flow!(break),
+        }
+    }
+
+    /// Whether the final byte `key` starts a bracketed paste, `CSI 200 ~`
+    pub fn starts_paste(&self, key: u8) -> bool {
+        key == b'~'
+            && !self.private
+            && self.intermediate.is_none()
+            && self.params.is_empty()
+            && self.current == Some(200)
+    }
+
+    fn push(&mut self) {
+        let mut values = std::mem::take(&mut self.values);
+
+        values.push(self.current.take().unwrap_or(0));
+        self.params.push(values.remove(0));
+        self.subparams.push(values);
+    }
+
+    fn event(&mut self, key: u8) -> Option<Event> {
+        self.push();
+
+        let event = match (self.private, self.intermediate, key, self.params.as_slice()) {
+            (false, None, b't', &[4, height, width]) => {
+                TerminalEvent::TextAreaPixels(Size::new(width, height))
+            }
+            (false, None, b't', &[6, height, width]) => {
+                TerminalEvent::CellPixels(Size::new(width, height))
+            }
+            (false, None, b't', &[8, rows, cols]) => {
+                TerminalEvent::TextAreaCells(Size::new(cols, rows))
+            }
+            (true, None, b'c', _) => TerminalEvent::DeviceAttributes,
+            (true, None, b'u', &[flags]) => TerminalEvent::KeyboardFlags(flags),
+            (true, Some(b'$'), b'y', &[mode, state]) => TerminalEvent::Mode { mode, state },
+            // Replies on the first row look like F3 with modifiers, `CSI 1 ; m R`
+            (_, None, b'R', &[row, col]) if self.private || row > 1 => {
+                TerminalEvent::CursorPosition { row, col }
+            }
+            (false, None, key, params) => return Keyboard::csi(params, &self.subparams, key),
+            _ => return None,
+        };
+
+        Some(Event::Terminal(event))
+    }
+}
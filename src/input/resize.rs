@@ -0,0 +1,117 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Time to wait for a burst of resize signals to settle
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Write end of the pipe used to forward signals to the watcher thread
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+/// Handler installed before ours, called if `chain` is enabled
+static PREVIOUS: AtomicUsize = AtomicUsize::new(libc::SIG_DFL);
+/// Whether the previous handler was installed with `SA_SIGINFO`
+static PREVIOUS_SIGINFO: AtomicBool = AtomicBool::new(false);
+
+type Handler = extern "C" fn(libc::c_int);
+type InfoHandler = extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void);
+
+extern "C" fn on_signal(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let fd = PIPE.load(Ordering::Relaxed);
+
+    if fd >= 0 {
+        // Only async-signal-safe calls here, errors are ignored:
+        // a full pipe already holds a pending notification.
+        unsafe { libc::write(fd, [0u8].as_ptr() as *const libc::c_void, 1) };
+    }
+
+    let previous = PREVIOUS.load(Ordering::Relaxed);
+
+    if previous != libc::SIG_DFL && previous != libc::SIG_IGN {
+        if PREVIOUS_SIGINFO.load(Ordering::Relaxed) {
+            let handler: InfoHandler = unsafe { std::mem::transmute(previous) };
+
+            handler(signal, info, context)
+        } else {
+            let handler: Handler = unsafe { std::mem::transmute(previous) };
+
+            handler(signal)
+        }
+    }
+}
+
+/// Watch for terminal resizes using SIGWINCH.
+/// This will block, so it should run from a dedicated thread.
+///
+/// Bursts of signals, as sent by terminals during a window drag, are debounced
+/// into a single `callback` call. If `chain` is true, the previously installed
+/// signal handler is still called for every signal.
+pub fn watch_resize<F>(chain: bool, mut callback: F) -> io::Result<()>
+where
+    F: FnMut(),
+{
+    let (read, write) = unsafe {
+        let mut fds = [0; 2];
+
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        (fds[0], fds[1])
+    };
+
+    unsafe {
+        libc::fcntl(write, libc::F_SETFL, libc::O_NONBLOCK);
+    }
+
+    PIPE.store(write, Ordering::Relaxed);
+
+    unsafe {
+        let mut action = MaybeUninit::<libc::sigaction>::zeroed().assume_init();
+        let mut previous = MaybeUninit::<libc::sigaction>::zeroed().assume_init();
+
+        action.sa_sigaction = on_signal as InfoHandler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        if libc::sigaction(libc::SIGWINCH, &action, &mut previous) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if chain {
+            // Set the kind of handler first, the signal could fire in between
+            PREVIOUS_SIGINFO.store(previous.sa_flags & libc::SA_SIGINFO != 0, Ordering::Relaxed);
+            PREVIOUS.store(previous.sa_sigaction, Ordering::Relaxed);
+        }
+    }
+
+    let mut buf = [0u8; 64];
+    let mut fds = libc::pollfd {
+        fd: read,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        // Wait for a first signal
+        if unsafe { libc::read(read, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } < 0 {
+            let error = io::Error::last_os_error();
+
+            match error.kind() {
+                io::ErrorKind::Interrupted => continue,
+                _ => return Err(error),
+            }
+        }
+
+        // Then drain the pipe until no signal came for the debounce duration
+        while unsafe { libc::poll(&mut fds, 1, DEBOUNCE.as_millis() as libc::c_int) } > 0 {
+            unsafe { libc::read(read, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        }
+
+        callback()
+    }
+}
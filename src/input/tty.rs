@@ -4,10 +4,30 @@ use std::io::Write;
 use std::mem::MaybeUninit;
 use std::os::fd::RawFd;
 use std::os::unix::prelude::AsRawFd;
+use std::time::Duration;
+
+use crate::gfx::Size;
+use crate::input::{TerminalEvent, query_terminal};
+use crate::output::{GraphemeKind, Widths};
+
+/// Keyboard enhancements requested from terminals supporting the kitty keyboard protocol:
+/// disambiguate escape codes, report repeat and release events,
+/// report all keys as escape codes along with their text.
+const KEYBOARD_FLAGS: u32 = 0b1_1011;
 
 pub struct Terminal {
     settings: Option<TerminalSettings>,
     alt_screen: bool,
+    /// Whether keyboard enhancement flags were pushed
+    keyboard: bool,
+    /// Whether mouse positions are reported in pixels (SGR-Pixels)
+    pixel_mouse: bool,
+    /// Whether grapheme clustering (mode 2027) was enabled
+    graphemes: bool,
+    /// Width of the graphemes the terminal measures differently than `unicode-width`
+    widths: Widths,
+    /// Cell size in pixels reported by the terminal, when TIOCGWINSZ doesn't
+    reported_cell_pixels: Option<Size<f32>>,
 }
 
 impl Drop for Terminal {
@@ -20,26 +40,105 @@ impl Terminal {
     /// Setup the input stream to operate in raw mode.
     /// Returns an object that'll revert terminal settings.
     pub fn setup() -> Self {
-        Self {
-            settings: match TerminalSettings::open_raw() {
-                Ok(settings) => Some(settings),
-                Err(error) => {
-                    tracing::error!("Failed to setup terminal: {error}");
+        let settings = match TerminalSettings::open_raw() {
+            Ok(settings) => Some(settings),
+            Err(error) => {
+                tracing::error!("Failed to setup terminal: {error}");
 
-                    None
-                }
-            },
-            alt_screen: if let Err(error) = TTY::enter_alt_screen() {
-                tracing::error!("Failed to enter alternative screen: {error}");
+                None
+            }
+        };
+        let alt_screen = if let Err(error) = TTY::enter_alt_screen() {
+            tracing::error!("Failed to enter alternative screen: {error}");
 
-                false
-            } else {
-                true
-            },
+            false
+        } else {
+            true
+        };
+        // Keyboard flags are kept per screen, push them on the alternative screen
+        let (keyboard, pixel_mouse, graphemes) = match TTY::enable_features() {
+            Ok(features) => features,
+            Err(error) => {
+                tracing::error!("Failed to enable terminal features: {error}");
+
+                (false, false, false)
+            }
+        };
+        // Measured once grapheme clustering is enabled, as it changes widths
+        let widths = match TTY::probe_widths() {
+            Ok(widths) => widths,
+            Err(error) => {
+                tracing::error!("Failed to measure grapheme widths: {error}");
+
+                Widths::default()
+            }
+        };
+
+        let reported_cell_pixels = match cell_pixels() {
+            Some(_) => None,
+            None => TTY::query_cell_pixels().unwrap_or_else(|error| {
+                tracing::error!("Failed to query terminal cell size: {error}");
+
+                None
+            }),
+        };
+
+        // Sent last, the replies are read by the browser
+        if let Err(error) = TTY::send_queries() {
+            tracing::error!("Failed to query terminal: {error}");
+        }
+
+        Self {
+            settings,
+            alt_screen,
+            keyboard,
+            pixel_mouse,
+            graphemes,
+            widths,
+            reported_cell_pixels,
         }
     }
 
+    /// Whether mouse positions are reported in pixels instead of cells
+    pub fn pixel_mouse(&self) -> bool {
+        self.pixel_mouse
+    }
+
+    /// Width of the graphemes the terminal measures differently than `unicode-width`
+    pub fn widths(&self) -> &Widths {
+        &self.widths
+    }
+
+    /// Cell size in pixels reported by the terminal, when TIOCGWINSZ doesn't
+    pub fn reported_cell_pixels(&self) -> Option<Size<f32>> {
+        self.reported_cell_pixels
+    }
+
     pub fn teardown(&mut self) {
+        if self.keyboard {
+            if let Err(error) = TTY::pop_keyboard_flags() {
+                tracing::error!("Failed to disable keyboard enhancements: {error}");
+            }
+
+            self.keyboard = false;
+        }
+
+        if self.pixel_mouse {
+            if let Err(error) = TTY::disable_pixel_mouse() {
+                tracing::error!("Failed to disable pixel mouse reports: {error}");
+            }
+
+            self.pixel_mouse = false;
+        }
+
+        if self.graphemes {
+            if let Err(error) = TTY::disable_graphemes() {
+                tracing::error!("Failed to disable grapheme clustering: {error}");
+            }
+
+            self.graphemes = false;
+        }
+
         if let Some(ref settings) = self.settings {
             if let Err(error) = settings.apply() {
                 tracing::error!("Failed to revert terminal settings: {error}");
@@ -58,12 +157,41 @@ impl Terminal {
     }
 }
 
+/// Size of a terminal cell in pixels, if the terminal reports its size in pixels
+pub fn cell_pixels() -> Option<Size<f32>> {
+    let size = unsafe {
+        let mut size = MaybeUninit::<libc::winsize>::uninit();
+
+        libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr())
+            .to_err()
+            .ok()?;
+
+        size.assume_init()
+    };
+    let (cells, pixels) = (
+        Size::new(size.ws_col, size.ws_row),
+        Size::new(size.ws_xpixel, size.ws_ypixel),
+    );
+
+    (cells.width > 0 && cells.height > 0 && pixels.width > 0 && pixels.height > 0)
+        .then(|| pixels.cast::<f32>() / cells.cast::<f32>())
+}
+
 enum TTY {
     Raw(RawFd),
     File(File),
 }
 
-const SEQUENCES: [(u32, bool); 4] = [(1049, true), (1003, true), (1006, true), (25, false)];
+const SEQUENCES: [(u32, bool); 6] = [
+    (1049, true),
+    (1003, true),
+    (1006, true),
+    (25, false),
+    // Bracketed paste
+    (2004, true),
+    // Focus events
+    (1004, true),
+];
 
 impl TTY {
     fn stdin() -> TTY {
@@ -85,12 +213,163 @@ impl TTY {
             write!(out, "\x1b[?{}{}", sequence, if enable { "h" } else { "l" })?;
         }
 
+        out.flush()
+    }
+
+    fn send_queries() -> io::Result<()> {
+        let mut out = io::stdout();
+
         // Set the current foreground color to black
         write!(out, "\x1b[48;2;0;0;0m")?;
         // Query current foreground color to for true-color support detection
         write!(out, "\x1bP$qm\x1b\\")?;
         // Query current terminal name
         write!(out, "\x1bP+q544e\x1b\\")?;
+        // Query the default foreground and background colors, and the 16 color palette
+        write!(out, "\x1b]10;?\x1b\\\x1b]11;?\x1b\\")?;
+
+        for index in 0..16 {
+            write!(out, "\x1b]4;{index};?\x1b\\")?;
+        }
+
+        out.flush()
+    }
+
+    /// Enable the kitty keyboard protocol, SGR-Pixels mouse reports and grapheme
+    /// clustering if the terminal supports them, returns which ones were enabled.
+    fn enable_features() -> io::Result<(bool, bool, bool)> {
+        let replies = query_terminal(
+            "\x1b[?u\x1b[?1016$p\x1b[?2027$p",
+            Duration::from_millis(200),
+        )?;
+        let keyboard = replies
+            .iter()
+            .any(|reply| matches!(reply, TerminalEvent::KeyboardFlags(_)));
+        // Pixel positions are converted to cells using the window size in pixels
+        let pixel_mouse = cell_pixels().is_some()
+            && replies.iter().any(
+                |reply| matches!(reply, TerminalEvent::Mode { mode: 1016, state } if *state != 0),
+            );
+        // Only enabled if it's supported but reset, it can also be permanently set
+        let graphemes = replies.iter().any(|reply| {
+            matches!(
+                reply,
+                TerminalEvent::Mode {
+                    mode: 2027,
+                    state: 2
+                }
+            )
+        });
+        let mut out = io::stdout();
+
+        if keyboard {
+            write!(out, "\x1b[>{KEYBOARD_FLAGS}u")?;
+        }
+        if pixel_mouse {
+            write!(out, "\x1b[?1016h")?;
+        }
+        if graphemes {
+            write!(out, "\x1b[?2027h")?;
+        }
+
+        out.flush()?;
+
+        Ok((keyboard, pixel_mouse, graphemes))
+    }
+
+    /// Print a sample of each kind of grapheme at the start of the second row,
+    /// and read the cursor position after it to measure its width.
+    ///
+    /// The first row is avoided, as cursor position reports on it can't be told
+    /// apart from F3 with modifiers.
+    fn probe_widths() -> io::Result<Widths> {
+        let mut queries = String::new();
+
+        for kind in GraphemeKind::ALL {
+            queries += &format!("\x1b[2;1H{}\x1b[6n", kind.sample());
+        }
+
+        // Erase the samples
+        queries += "\x1b[2;1H\x1b[2K";
+
+        let positions = query_terminal(&queries, Duration::from_millis(200))?
+            .into_iter()
+            .filter_map(|reply| match reply {
+                TerminalEvent::CursorPosition { row: 2, col } => Some(col.saturating_sub(1)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if positions.len() != GraphemeKind::ALL.len() {
+            tracing::warn!("unexpected cursor position replies: {positions:?}");
+
+            return Ok(Widths::default());
+        }
+
+        let measured = GraphemeKind::ALL
+            .into_iter()
+            .zip(positions.into_iter().map(|col| col as usize))
+            .collect::<Vec<_>>();
+        let widths = Widths::from_probe(&measured);
+
+        tracing::debug!("grapheme widths: {measured:?}, overrides: {widths}");
+
+        Ok(widths)
+    }
+
+    /// Query the cell size, text area size in pixels, and text area size in cells.
+    ///
+    /// Used when TIOCGWINSZ doesn't report pixel sizes, as some terminals
+    /// can only report the size of their text area in pixels.
+    fn query_cell_pixels() -> io::Result<Option<Size<f32>>> {
+        let replies = query_terminal("\x1b[16t\x1b[14t\x1b[18t", Duration::from_millis(200))?;
+        let (mut cell, mut pixels, mut cells) = (None, None, None);
+
+        for reply in replies {
+            match reply {
+                TerminalEvent::CellPixels(size) => cell = Some(size),
+                TerminalEvent::TextAreaPixels(size) => pixels = Some(size),
+                TerminalEvent::TextAreaCells(size) => cells = Some(size),
+                _ => (),
+            }
+        }
+
+        let valid = |size: &Size| size.width > 0 && size.height > 0;
+        let cell = match (
+            cell.filter(valid),
+            pixels.filter(valid),
+            cells.filter(valid),
+        ) {
+            (Some(cell), _, _) => cell.cast::<f32>(),
+            (None, Some(pixels), Some(cells)) => pixels.cast::<f32>() / cells.cast::<f32>(),
+            _ => return Ok(None),
+        };
+
+        tracing::debug!("terminal reported a cell size of {cell:?}");
+
+        Ok(Some(cell))
+    }
+
+    fn disable_graphemes() -> io::Result<()> {
+        let mut out = io::stdout();
+
+        write!(out, "\x1b[?2027l")?;
+
+        out.flush()
+    }
+
+    fn disable_pixel_mouse() -> io::Result<()> {
+        let mut out = io::stdout();
+
+        write!(out, "\x1b[?1016l")?;
+
+        out.flush()
+    }
+
+    fn pop_keyboard_flags() -> io::Result<()> {
+        let mut out = io::stdout();
+
+        write!(out, "\x1b[<u")?;
 
         out.flush()
     }
@@ -102,6 +381,9 @@ impl TTY {
             write!(out, "\x1b[?{}{}", sequence, if enable { "l" } else { "h" })?;
         }
 
+        // Restore the default cursor shape
+        write!(out, "\x1b[0 q")?;
+
         out.flush()
     }
 
@@ -5,4 +5,4 @@ pub mod input;
 pub mod output;
 pub mod ui;
 
-mod utils;
+pub mod utils;
@@ -1,18 +1,28 @@
 // mod kd_tree;
 // mod quantizer;
 mod cell;
+mod color_filter;
+mod downsample;
 mod frame_sync;
+mod grid;
 mod painter;
 mod quad;
 mod render_thread;
 mod renderer;
+mod widths;
 mod window;
+mod writer;
 mod xterm;
 
 pub use cell::*;
+pub use color_filter::*;
+pub use downsample::*;
 pub use frame_sync::*;
+pub use grid::*;
 pub use painter::*;
 pub use quad::*;
 pub use render_thread::*;
 pub use renderer::*;
+pub use widths::*;
 pub use window::*;
+pub use writer::*;
@@ -1,36 +1,104 @@
-use std::rc::Rc;
+use std::{collections::HashMap, num::NonZeroU32};
 
-use crate::gfx::{Color, Point};
+use crate::gfx::Color;
 
-#[derive(Clone, PartialEq)]
+/// Handle to a grapheme string, see [`Graphemes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphemeId(NonZeroU32);
+
+/// Text grapheme drawn in a terminal cell
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Grapheme {
-    /// Unicode character in UTF-8, might contain multiple code points (Emoji, CJK).
-    pub char: String,
-    pub index: usize,
-    pub width: usize,
-    pub color: Color,
+    /// Unicode character, might contain multiple code points (Emoji, CJK).
+    pub char: GraphemeId,
+    /// Color packed using `Color::pack`
+    pub color: u32,
+    /// Index of the cell in the grapheme, for graphemes wider than one cell
+    pub index: u8,
+    pub width: u8,
+    /// Distance in pixels between the text baseline and the cell middle,
+    /// used to pick a grapheme when text runs overlap.
+    pub offset: u8,
+}
+
+impl Grapheme {
+    pub fn color(&self) -> Color {
+        Color::unpack(self.color)
+    }
 }
 
-/// Terminal cell with `height = width * 2`
-#[derive(PartialEq)]
-pub struct Cell {
-    pub cursor: Point<u32>,
-    /// Text grapheme if any
-    pub grapheme: Option<Rc<Grapheme>>,
-    pub quadrant: (Color, Color, Color, Color),
+const ASCII_BYTES: [u8; 128] = {
+    let mut ascii = [0u8; 128];
+    let mut index = 0;
+
+    while index < ascii.len() {
+        ascii[index] = index as u8;
+        index += 1;
+    }
+
+    ascii
+};
+/// Every ASCII character, used to store them without a table entry
+const ASCII: &str = match std::str::from_utf8(&ASCII_BYTES) {
+    Ok(ascii) => ascii,
+    Err(_) => panic!("invalid ASCII table"),
+};
+
+/// Interned grapheme strings.
+///
+/// ASCII characters are encoded in their handle, other graphemes are
+/// stored once and then looked up without allocating.
+#[derive(Default)]
+pub struct Graphemes {
+    strings: Vec<Box<str>>,
+    ids: HashMap<Box<str>, GraphemeId>,
 }
 
-impl Cell {
-    pub fn new(x: u32, y: u32) -> Cell {
-        Cell {
-            cursor: Point::new(x, y),
-            grapheme: None,
-            quadrant: (
-                Color::black(),
-                Color::black(),
-                Color::black(),
-                Color::black(),
-            ),
+impl Graphemes {
+    /// Get the handle of a grapheme, adding it to the table if needed
+    pub fn intern(&mut self, grapheme: &str) -> GraphemeId {
+        if let &[byte] = grapheme.as_bytes()
+            && byte.is_ascii()
+        {
+            return Self::id(byte as usize);
+        }
+
+        if let Some(&id) = self.ids.get(grapheme) {
+            return id;
+        }
+
+        let id = Self::id(ASCII.len() + self.strings.len());
+
+        self.strings.push(grapheme.into());
+        self.ids.insert(grapheme.into(), id);
+
+        id
+    }
+
+    /// Get the string of an interned grapheme
+    pub fn get(&self, id: GraphemeId) -> &str {
+        match id.0.get() as usize - 1 {
+            index if index < ASCII.len() => &ASCII[index..index + 1],
+            index => &self.strings[index - ASCII.len()],
         }
     }
+
+    /// Number of graphemes stored in the table
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Remove every grapheme from the table, invalidating existing handles
+    pub fn clear(&mut self) {
+        self.strings.clear();
+        self.ids.clear();
+    }
+
+    fn id(index: usize) -> GraphemeId {
+        GraphemeId(NonZeroU32::new(index as u32 + 1).expect("grapheme table overflow"))
+    }
 }
@@ -0,0 +1,222 @@
+use std::str::FromStr;
+
+use crate::gfx::{Color, LINEAR_MAX};
+
+/// A color transformation applied to the page before painting it.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    /// Invert the lightness while preserving the hue.
+    /// Cells that look like images are left untouched if `spare_images` is set.
+    Invert {
+        spare_images: bool,
+    },
+    Grayscale,
+    Sepia,
+    /// Warm tint to reduce blue light
+    Night,
+    HighContrast,
+    /// Simulate green-blindness
+    Deuteranopia,
+    /// Simulate red-blindness
+    Protanopia,
+}
+
+type Matrix = [[f32; 3]; 3];
+
+const LINEAR_TO_LMS: Matrix = [
+    [0.41222146, 0.53633255, 0.051445995],
+    [0.2119035, 0.6806995, 0.10739696],
+    [0.08830246, 0.28171885, 0.6299787],
+];
+const LMS_TO_OKLAB: Matrix = [
+    [0.21045426, 0.7936178, -0.004072047],
+    [1.9779985, -2.4285922, 0.4505937],
+    [0.025904037, 0.78277177, -0.80867577],
+];
+const OKLAB_TO_LMS: Matrix = [
+    [1.0, 0.39633778, 0.21580376],
+    [1.0, -0.105561346, -0.06385417],
+    [1.0, -0.08948418, -1.2914855],
+];
+const LMS_TO_LINEAR: Matrix = [
+    [4.0767417, -3.3077116, 0.23096994],
+    [-1.268438, 2.6097574, -0.34131938],
+    [-0.0041960864, -0.7034186, 1.7076147],
+];
+const SEPIA: Matrix = [
+    [0.393, 0.769, 0.189],
+    [0.349, 0.686, 0.168],
+    [0.272, 0.534, 0.131],
+];
+// Color vision deficiency simulation from Machado et al. (2009), severity 1.0
+const DEUTERANOPIA: Matrix = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+const PROTANOPIA: Matrix = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+
+/// Minimum average chroma for a cell to be considered part of an image
+const IMAGE_CHROMA: f32 = 0.1;
+
+impl ColorFilter {
+    /// Filters in the order they're cycled through at runtime
+    const CYCLE: [ColorFilter; 9] = [
+        ColorFilter::None,
+        ColorFilter::Invert { spare_images: true },
+        ColorFilter::Invert {
+            spare_images: false,
+        },
+        ColorFilter::Grayscale,
+        ColorFilter::Sepia,
+        ColorFilter::Night,
+        ColorFilter::HighContrast,
+        ColorFilter::Deuteranopia,
+        ColorFilter::Protanopia,
+    ];
+
+    /// Get the filter following this one
+    pub fn next(&self) -> ColorFilter {
+        let index = Self::CYCLE
+            .iter()
+            .position(|filter| filter == self)
+            .unwrap_or(0);
+
+        Self::CYCLE[(index + 1) % Self::CYCLE.len()]
+    }
+
+    /// Filter the colors of a terminal cell, made of a quadrant of background
+    /// pixels and the color of its grapheme if any.
+    pub fn apply_cell(
+        &self,
+        quadrant: (Color, Color, Color, Color),
+        foreground: Option<Color>,
+    ) -> ((Color, Color, Color, Color), Option<Color>) {
+        if *self == ColorFilter::None {
+            return (quadrant, foreground);
+        }
+
+        if let ColorFilter::Invert { spare_images: true } = self {
+            let (x, y, z, w) = quadrant;
+            let chroma = [x, y, z, w]
+                .into_iter()
+                .map(|color| {
+                    let lab = oklab(color);
+
+                    lab.g.hypot(lab.b)
+                })
+                .sum::<f32>();
+
+            // Saturated cells are most likely images, inverting them looks off
+            if chroma / 4.0 > IMAGE_CHROMA {
+                return (quadrant, foreground);
+            }
+        }
+
+        let (x, y, z, w) = quadrant;
+
+        (
+            (self.apply(x), self.apply(y), self.apply(z), self.apply(w)),
+            foreground.map(|color| self.apply(color)),
+        )
+    }
+
+    /// Filter a single color
+    pub fn apply(&self, color: Color) -> Color {
+        match self {
+            ColorFilter::None => color,
+            ColorFilter::Invert { .. } => {
+                let lab = oklab(color);
+
+                from_oklab(Color::new(1.0 - lab.r, lab.g, lab.b))
+            }
+            ColorFilter::Grayscale => {
+                let lab = oklab(color);
+
+                from_oklab(Color::new(lab.r, 0.0, 0.0))
+            }
+            ColorFilter::HighContrast => {
+                let lab = oklab(color);
+
+                from_oklab(Color::new(
+                    (lab.r - 0.5).mul_add(1.6, 0.5).clamp(0.0, 1.0),
+                    lab.g * 1.2,
+                    lab.b * 1.2,
+                ))
+            }
+            ColorFilter::Sepia => from_srgb(transform(&SEPIA, srgb(color))),
+            ColorFilter::Night => from_linear(linear(color) * (0.9, 0.7, 0.45)),
+            ColorFilter::Deuteranopia => from_linear(transform(&DEUTERANOPIA, linear(color))),
+            ColorFilter::Protanopia => from_linear(transform(&PROTANOPIA, linear(color))),
+        }
+    }
+}
+
+impl FromStr for ColorFilter {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "none" => ColorFilter::None,
+            "invert" => ColorFilter::Invert { spare_images: true },
+            "invert-all" => ColorFilter::Invert {
+                spare_images: false,
+            },
+            "grayscale" => ColorFilter::Grayscale,
+            "sepia" => ColorFilter::Sepia,
+            "night" => ColorFilter::Night,
+            "high-contrast" => ColorFilter::HighContrast,
+            "deuteranopia" => ColorFilter::Deuteranopia,
+            "protanopia" => ColorFilter::Protanopia,
+            _ => return Err(format!("unknown color filter: {value}")),
+        })
+    }
+}
+
+fn transform(matrix: &Matrix, color: Color<f32>) -> Color<f32> {
+    Color::new(
+        color.dot(matrix[0]),
+        color.dot(matrix[1]),
+        color.dot(matrix[2]),
+    )
+}
+
+fn srgb(color: Color) -> Color<f32> {
+    color.cast::<f32>() / 255.0
+}
+
+fn from_srgb(color: Color<f32>) -> Color {
+    (color.clamp(0.0, 1.0) * 255.0).round().cast()
+}
+
+fn linear(color: Color) -> Color<f32> {
+    color.to_linear().cast::<f32>() / LINEAR_MAX as f32
+}
+
+fn from_linear(color: Color<f32>) -> Color {
+    (color.clamp(0.0, 1.0) * LINEAR_MAX as f32)
+        .round()
+        .cast::<u16>()
+        .to_srgb()
+}
+
+/// Convert to OKLab, stored as `L`, `a`, `b` in the `r`, `g`, `b` fields
+fn oklab(color: Color) -> Color<f32> {
+    transform(
+        &LMS_TO_OKLAB,
+        transform(&LINEAR_TO_LMS, linear(color)).map(f32::cbrt),
+    )
+}
+
+fn from_oklab(lab: Color<f32>) -> Color {
+    from_linear(transform(
+        &LMS_TO_LINEAR,
+        transform(&OKLAB_TO_LMS, lab).map(|value| value * value * value),
+    ))
+}
@@ -0,0 +1,246 @@
+//! Bitmap downsampling kernels, picked at runtime.
+//!
+//! - AVX2 on x86, averaging 8 pixels at once using gather instructions
+//!   for the transfer function tables.
+//! - NEON on aarch64, copying runs of 16 identical pixels at once. NEON has no
+//!   gather instructions, so other pixels are averaged by the scalar version.
+//! - Scalar everywhere else. SSE2 is skipped on purpose: without gathers, table
+//!   lookups stay scalar and it's no faster than the scalar version.
+
+use std::sync::LazyLock;
+
+use crate::gfx::{Color, LINEAR_MAX};
+
+/// sRGB transfer function tables widened to 32 bits, as used by gather instructions
+struct Tables {
+    to_linear: [u32; 256],
+    to_srgb: [u32; LINEAR_MAX as usize + 1],
+    /// Whether converting to linear light and back gives the original value,
+    /// in which case identical pixels can be copied instead of averaged.
+    round_trip: bool,
+}
+
+static TABLES: LazyLock<Tables> = LazyLock::new(|| {
+    let mut tables = Tables {
+        to_linear: [0; 256],
+        to_srgb: [0; LINEAR_MAX as usize + 1],
+        round_trip: false,
+    };
+
+    for (srgb, linear) in tables.to_linear.iter_mut().enumerate() {
+        *linear = Color::new(srgb as u8, 0, 0).to_linear().r as u32;
+    }
+
+    for (linear, srgb) in tables.to_srgb.iter_mut().enumerate() {
+        *srgb = Color::<u16>::new(linear as u16, 0, 0).to_srgb().r as u32;
+    }
+
+    tables.round_trip = (0..256).all(|srgb| {
+        let linear = tables.to_linear[srgb] as usize;
+
+        tables.to_srgb[linear] as usize == srgb
+    });
+
+    tables
+});
+
+/// Average the BGRA pixels of rows `upper` and `lower` two by two, in linear light.
+///
+/// Gives the same result as `Color::average` on each pair of pixels,
+/// writing one color to `output` for every pixel of the shortest input.
+pub fn average_rows(upper: &[u8], lower: &[u8], output: &mut [Color]) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 support was checked above
+        return unsafe { x86::average_rows(upper, lower, output) };
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // Safety: NEON support was checked above
+        return unsafe { neon::average_rows(upper, lower, output) };
+    }
+
+    average_rows_scalar(upper, lower, output)
+}
+
+/// Portable version of [`average_rows`], used when no SIMD support is detected.
+pub fn average_rows_scalar(upper: &[u8], lower: &[u8], output: &mut [Color]) {
+    let tables = &*TABLES;
+
+    for ((upper, lower), color) in upper
+        .chunks_exact(4)
+        .zip(lower.chunks_exact(4))
+        .zip(output.iter_mut())
+    {
+        if tables.round_trip
+            && u32::from_ne_bytes([upper[0], upper[1], upper[2], upper[3]])
+                == u32::from_ne_bytes([lower[0], lower[1], lower[2], lower[3]])
+        {
+            *color = Color::new(upper[2], upper[1], upper[0]);
+
+            continue;
+        }
+
+        let channel = |index: usize| {
+            let sum =
+                tables.to_linear[upper[index] as usize] + tables.to_linear[lower[index] as usize];
+
+            tables.to_srgb[(sum / 2) as usize] as u8
+        };
+
+        *color = Color::new(channel(2), channel(1), channel(0));
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::{Color, TABLES, average_rows_scalar};
+
+    /// Average 8 pixels per iteration, with one pixel per 32 bits lane.
+    ///
+    /// Channels are converted using gather instructions, which SSE2 lacks.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn average_rows(upper: &[u8], lower: &[u8], output: &mut [Color]) {
+        let tables = &*TABLES;
+        let pixels = output.len().min(upper.len() / 4).min(lower.len() / 4);
+        let chunks = pixels / 8;
+        let to_linear = tables.to_linear.as_ptr() as *const i32;
+        let to_srgb = tables.to_srgb.as_ptr() as *const i32;
+        let mask = _mm256_set1_epi32(0xff);
+
+        for chunk in 0..chunks {
+            let offset = chunk * 32;
+
+            // Safety: both rows hold at least `pixels * 4` bytes, and table indices
+            // are bytes for `to_linear`, averages of at most `LINEAR_MAX` for `to_srgb`.
+            unsafe {
+                let a = _mm256_loadu_si256(upper.as_ptr().add(offset) as *const __m256i);
+                let b = _mm256_loadu_si256(lower.as_ptr().add(offset) as *const __m256i);
+
+                if tables.round_trip && _mm256_movemask_epi8(_mm256_cmpeq_epi8(a, b)) == -1 {
+                    store(a, output.as_mut_ptr().add(chunk * 8));
+                } else {
+                    // Average the blue, green and red channels, in place
+                    let average = |shift: __m128i| {
+                        let a = _mm256_and_si256(_mm256_srl_epi32(a, shift), mask);
+                        let b = _mm256_and_si256(_mm256_srl_epi32(b, shift), mask);
+                        let sum = _mm256_add_epi32(
+                            _mm256_i32gather_epi32::<4>(to_linear, a),
+                            _mm256_i32gather_epi32::<4>(to_linear, b),
+                        );
+                        let srgb =
+                            _mm256_i32gather_epi32::<4>(to_srgb, _mm256_srli_epi32::<1>(sum));
+
+                        _mm256_sll_epi32(srgb, shift)
+                    };
+                    let bgr = _mm256_or_si256(
+                        _mm256_or_si256(
+                            average(_mm_cvtsi32_si128(0)),
+                            average(_mm_cvtsi32_si128(8)),
+                        ),
+                        average(_mm_cvtsi32_si128(16)),
+                    );
+
+                    store(bgr, output.as_mut_ptr().add(chunk * 8));
+                }
+            }
+        }
+
+        let done = chunks * 8;
+
+        average_rows_scalar(
+            &upper[done * 4..],
+            &lower[done * 4..],
+            &mut output[done..pixels],
+        )
+    }
+
+    /// Write 8 BGRA pixels to `output` as RGB colors, 12 bytes per 128 bits lane.
+    #[target_feature(enable = "avx2")]
+    unsafe fn store(bgra: __m256i, output: *mut Color) {
+        let shuffle = _mm256_setr_epi8(
+            2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1, //
+            2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1,
+        );
+        let rgb = _mm256_shuffle_epi8(bgra, shuffle);
+        let output = output as *mut u8;
+
+        // Safety: `Color` is 3 bytes, the caller guarantees room for 8 of them
+        unsafe {
+            for (lane, rgb) in [
+                _mm256_castsi256_si128(rgb),
+                _mm256_extracti128_si256::<1>(rgb),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let output = output.add(lane * 12);
+
+                _mm_storel_epi64(output as *mut __m128i, rgb);
+                (output.add(8) as *mut i32).write_unaligned(_mm_extract_epi32::<2>(rgb));
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{Color, TABLES, average_rows_scalar};
+
+    /// Copy 16 pixels per iteration when both rows are identical,
+    /// deinterleaving BGRA into RGB with a single store.
+    ///
+    /// Other chunks are averaged by the scalar version, as converting channels
+    /// would need gather instructions.
+    #[target_feature(enable = "neon")]
+    pub unsafe fn average_rows(upper: &[u8], lower: &[u8], output: &mut [Color]) {
+        let pixels = output.len().min(upper.len() / 4).min(lower.len() / 4);
+        let chunks = match TABLES.round_trip {
+            true => pixels / 16,
+            false => 0,
+        };
+
+        for chunk in 0..chunks {
+            let (offset, start) = (chunk * 64, chunk * 16);
+
+            // Safety: both rows hold at least `pixels * 4` bytes,
+            // and `Color` is 3 bytes with room for `pixels` of them.
+            unsafe {
+                let a = vld4q_u8(upper.as_ptr().add(offset));
+                let b = vld4q_u8(lower.as_ptr().add(offset));
+                let equal = vandq_u8(
+                    vandq_u8(vceqq_u8(a.0, b.0), vceqq_u8(a.1, b.1)),
+                    vandq_u8(vceqq_u8(a.2, b.2), vceqq_u8(a.3, b.3)),
+                );
+
+                if vminvq_u8(equal) == 0xff {
+                    let rgb = uint8x16x3_t(a.2, a.1, a.0);
+
+                    vst3q_u8(output.as_mut_ptr().add(start) as *mut u8, rgb);
+                } else {
+                    average_rows_scalar(
+                        &upper[offset..offset + 64],
+                        &lower[offset..offset + 64],
+                        &mut output[start..start + 16],
+                    )
+                }
+            }
+        }
+
+        let done = chunks * 16;
+
+        average_rows_scalar(
+            &upper[done * 4..],
+            &lower[done * 4..],
+            &mut output[done..pixels],
+        )
+    }
+}
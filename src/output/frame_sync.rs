@@ -14,6 +14,11 @@ impl FrameSync {
         }
     }
 
+    /// Change the FPS, starting with the next frame
+    pub fn set_fps(&mut self, fps: f32) {
+        self.frame_duration = Duration::from_micros((1_000_000.0 / fps) as u64);
+    }
+
     /// Mark the beginning of the render
     pub fn start(&mut self) {
         self.render_start = Some(Instant::now());
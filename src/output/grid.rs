@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+use crate::{
+    gfx::{Point, Size},
+    utils::parallel_map,
+};
+
+use super::{ColorFilter, Glyph, Grapheme, GraphemeId, Graphemes};
+
+/// Number of interned graphemes above which the table is reset
+const MAX_GRAPHEMES: usize = 64 * 1024;
+
+/// Terminal cells stored as a struct of arrays, row by row.
+///
+/// Keeps the last painted state of each cell along with per-row dirty bits,
+/// so that unchanged rows are skipped without comparing their cells.
+#[derive(Default)]
+pub struct Grid {
+    size: Size<usize>,
+    /// Colors of the top left, top right, bottom right and bottom left
+    /// quarters of each cell, packed using `Color::pack`
+    quadrants: Vec<[u32; 4]>,
+    /// Text grapheme of each cell, if any
+    text: Vec<Option<Grapheme>>,
+    painted_quadrants: Vec<[u32; 4]>,
+    painted_text: Vec<Option<Grapheme>>,
+    /// Rows changed since they were last painted, one bit per row
+    dirty: Vec<u64>,
+    /// Paint every cell on the next call to `changes`
+    repaint: bool,
+    graphemes: Graphemes,
+}
+
+impl Grid {
+    pub fn new(size: Size) -> Grid {
+        let size = size.cast::<usize>();
+        let cells = size.width * size.height;
+
+        Grid {
+            size,
+            quadrants: vec![[0; 4]; cells],
+            text: vec![None; cells],
+            painted_quadrants: vec![[0; 4]; cells],
+            painted_text: vec![None; cells],
+            dirty: vec![0; size.height.div_ceil(64)],
+            repaint: true,
+            graphemes: Graphemes::default(),
+        }
+    }
+
+    /// Size of the grid in cells
+    pub fn size(&self) -> Size {
+        self.size.cast()
+    }
+
+    /// Paint every cell on the next frame, even if unchanged
+    pub fn repaint(&mut self) {
+        self.repaint = true
+    }
+
+    /// Text of every cell, row by row
+    pub fn text(&self) -> &[Option<Grapheme>] {
+        &self.text
+    }
+
+    /// Get mutable access to the cells of consecutive rows, marking them as changed
+    pub fn rows_mut(&mut self, rows: Range<usize>) -> (&mut [[u32; 4]], &mut [Option<Grapheme>]) {
+        let rows = rows.start.min(self.size.height)..rows.end.min(self.size.height);
+        let cells = rows.start * self.size.width..rows.end * self.size.width;
+
+        for row in rows {
+            self.dirty[row / 64] |= 1 << (row % 64)
+        }
+
+        (&mut self.quadrants[cells.clone()], &mut self.text[cells])
+    }
+
+    /// Remove the text of every cell
+    pub fn clear_text(&mut self) {
+        let width = self.size.width.max(1);
+
+        for row in 0..self.size.height {
+            if self.text[row * width..][..self.size.width]
+                .iter()
+                .any(Option::is_some)
+            {
+                self.rows_mut(row..row + 1).1.fill(None)
+            }
+        }
+
+        // No text is left on the grid, but painted cells might still use the table
+        if self.graphemes.len() > MAX_GRAPHEMES {
+            self.graphemes.clear();
+            self.painted_text.fill(None);
+            self.repaint = true;
+        }
+    }
+
+    /// Get the handle of a grapheme string
+    pub fn intern(&mut self, grapheme: &str) -> GraphemeId {
+        self.graphemes.intern(grapheme)
+    }
+
+    /// Collect the glyphs of cells changed since the last call, in bands of `band` rows,
+    /// and remember them as painted. Bands are processed in parallel.
+    ///
+    /// The first row holds the navigation bar, it's painted without `filter`.
+    pub fn changes(&mut self, band: usize, filter: ColorFilter) -> Vec<Vec<Glyph<'_>>> {
+        let width = self.size.width.max(1);
+        let band = band.max(1) * width;
+        let repaint = std::mem::take(&mut self.repaint);
+        let dirty = &self.dirty;
+        let graphemes = &self.graphemes;
+        let bands = self
+            .quadrants
+            .chunks(band)
+            .zip(self.text.chunks(band))
+            .zip(self.painted_quadrants.chunks_mut(band))
+            .zip(self.painted_text.chunks_mut(band))
+            .enumerate()
+            .collect();
+
+        let glyphs = parallel_map(
+            bands,
+            |(index, (((quadrants, text), painted_quadrants), painted_text))| {
+                let mut glyphs = Vec::new();
+                let top = index * band / width;
+                let rows = quadrants
+                    .chunks(width)
+                    .zip(text.chunks(width))
+                    .zip(painted_quadrants.chunks_mut(width))
+                    .zip(painted_text.chunks_mut(width));
+
+                for (y, (((quadrants, text), painted_quadrants), painted_text)) in (top..).zip(rows)
+                {
+                    if !repaint && dirty[y / 64] & (1 << (y % 64)) == 0 {
+                        continue;
+                    }
+
+                    let filter = match y {
+                        0 => ColorFilter::None,
+                        _ => filter,
+                    };
+
+                    for (x, (((quadrant, text), painted_quadrant), painted_text)) in quadrants
+                        .iter()
+                        .zip(text)
+                        .zip(painted_quadrants.iter_mut())
+                        .zip(painted_text.iter_mut())
+                        .enumerate()
+                    {
+                        if !repaint && quadrant == painted_quadrant && text == painted_text {
+                            continue;
+                        }
+
+                        *painted_quadrant = *quadrant;
+                        *painted_text = *text;
+
+                        let cursor = Point::new(x, y).cast();
+
+                        glyphs.extend(Glyph::new(cursor, *quadrant, *text, graphemes, filter));
+                    }
+                }
+
+                glyphs
+            },
+        );
+
+        self.dirty.fill(0);
+
+        glyphs
+    }
+}
@@ -1,87 +1,67 @@
-use std::io::{self, Stdout, Write};
+use std::{
+    io::{self, Write},
+    mem,
+};
 
-use crate::gfx::{Color, Point};
+use crate::{
+    gfx::{Color, Point},
+    utils::{base64, parallel_map},
+};
 
-use super::{Cell, binarize_quandrant};
+use super::{ColorFilter, Grapheme, Graphemes, Writer, binarize_quandrant};
 
-pub struct Painter {
-    output: Stdout,
-    buffer: Vec<u8>,
-    cursor: Option<Point<u32>>,
-    true_color: bool,
-    background: Option<Color>,
-    foreground: Option<Color>,
-    background_code: Option<u8>,
-    foreground_code: Option<u8>,
+/// Shape of the terminal cursor, set using DECSCUSR.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
 }
 
-impl Painter {
-    pub fn new() -> Painter {
-        Painter {
-            buffer: Vec::new(),
-            cursor: None,
-            output: io::stdout(),
-            background: None,
-            foreground: None,
-            background_code: None,
-            foreground_code: None,
-            true_color: matches!(
-                std::env::var("COLORTERM").unwrap_or_default().as_str(),
-                "truecolor" | "24bit"
-            ),
+impl CursorShape {
+    /// DECSCUSR code for a blinking cursor of this shape
+    fn code(&self) -> u8 {
+        match self {
+            CursorShape::Block => 1,
+            CursorShape::Underline => 3,
+            CursorShape::Bar => 5,
         }
     }
+}
 
-    pub fn true_color(&self) -> bool {
-        self.true_color
-    }
-
-    pub fn set_true_color(&mut self, true_color: bool) {
-        self.true_color = true_color
-    }
-
-    pub fn begin(&mut self) -> io::Result<()> {
-        write!(self.buffer, "\x1b[?25l\x1b[?12l")
-    }
-
-    pub fn end(&mut self, cursor: Option<Point>) -> io::Result<()> {
-        if let Some(cursor) = cursor {
-            write!(
-                self.buffer,
-                "\x1b[{};{}H\x1b[?25h\x1b[?12h",
-                cursor.y + 1,
-                cursor.x + 1
-            )?;
-        }
-
-        self.output.write_all(self.buffer.as_slice())?;
-        self.output.flush()?;
-        self.buffer.clear();
-        self.cursor = None;
-
-        Ok(())
-    }
+/// A cell resolved to the character and colors written to the terminal.
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph<'a> {
+    pub cursor: Point<u32>,
+    pub char: &'a str,
+    pub background: Color,
+    pub foreground: Color,
+    pub width: u32,
+}
 
-    pub fn paint(&mut self, cell: &Cell) -> io::Result<()> {
-        let &Cell {
-            cursor,
-            quadrant,
-            ref grapheme,
-        } = cell;
+impl<'a> Glyph<'a> {
+    /// Pick the glyph for a cell, `None` if the cell is covered by a wide grapheme.
+    pub fn new(
+        cursor: Point<u32>,
+        quadrant: [u32; 4],
+        grapheme: Option<Grapheme>,
+        graphemes: &'a Graphemes,
+        filter: ColorFilter,
+    ) -> Option<Glyph<'a>> {
+        let quadrant = quadrant.map(Color::unpack);
+        let quadrant = (quadrant[0], quadrant[1], quadrant[2], quadrant[3]);
+        let (quadrant, grapheme_color) =
+            filter.apply_cell(quadrant, grapheme.map(|grapheme| grapheme.color()));
 
         let (char, background, foreground, width) = if let Some(grapheme) = grapheme {
             if grapheme.index > 0 {
-                return Ok(());
+                return None;
             }
 
             (
-                grapheme.char.as_str(),
-                quadrant
-                    .0
-                    .avg_with(quadrant.1)
-                    .avg_with(quadrant.2)
-                    .avg_with(quadrant.3),
-                grapheme.color,
+                graphemes.get(grapheme.char),
+                Color::average([quadrant.0, quadrant.1, quadrant.2, quadrant.3]),
+                grapheme_color.unwrap_or(grapheme.color()),
                 grapheme.width as u32,
             )
         } else {
@@ -90,8 +70,55 @@ impl Painter {
             (char, background, foreground, 1)
         };
 
+        Some(Glyph {
+            cursor,
+            char,
+            background,
+            foreground,
+            width,
+        })
+    }
+}
+
+/// Terminal state tracked to skip redundant escape sequences.
+#[derive(Clone, Copy, Debug, Default)]
+struct PaintState {
+    cursor: Option<Point<u32>>,
+    background: Option<Color>,
+    foreground: Option<Color>,
+    background_code: Option<u8>,
+    foreground_code: Option<u8>,
+}
+
+impl PaintState {
+    /// State left by encoding a sequence of glyphs ending with `glyph`.
+    ///
+    /// Without true color, the xterm codes always match the current colors,
+    /// so the last glyph is enough to know them.
+    fn after(mut self, glyph: &Glyph, true_color: bool) -> PaintState {
+        self.cursor = Some(glyph.cursor + Point::new(glyph.width, 0));
+        self.background = Some(glyph.background);
+        self.foreground = Some(glyph.foreground);
+
+        if !true_color {
+            self.background_code = Some(glyph.background.to_xterm());
+            self.foreground_code = Some(glyph.foreground.to_xterm());
+        }
+
+        self
+    }
+
+    fn encode(&mut self, glyph: &Glyph, true_color: bool, buffer: &mut Vec<u8>) -> io::Result<()> {
+        let &Glyph {
+            cursor,
+            char,
+            background,
+            foreground,
+            width,
+        } = glyph;
+
         if self.cursor != Some(cursor) {
-            write!(self.buffer, "\x1b[{};{}H", cursor.y + 1, cursor.x + 1)?;
+            write!(buffer, "\x1b[{};{}H", cursor.y + 1, cursor.x + 1)?;
         };
 
         self.cursor = Some(cursor + Point::new(width, 0));
@@ -99,9 +126,9 @@ impl Painter {
         if self.background != Some(background) {
             self.background = Some(background);
 
-            if self.true_color {
+            if true_color {
                 write!(
-                    self.buffer,
+                    buffer,
                     "\x1b[48;2;{};{};{}m",
                     background.r, background.g, background.b,
                 )?
@@ -111,7 +138,7 @@ impl Painter {
                 if self.background_code != Some(code) {
                     self.background_code = Some(code);
 
-                    write!(self.buffer, "\x1b[48;5;{code}m")?
+                    write!(buffer, "\x1b[48;5;{code}m")?
                 }
             }
         }
@@ -119,9 +146,9 @@ impl Painter {
         if self.foreground != Some(foreground) {
             self.foreground = Some(foreground);
 
-            if self.true_color {
+            if true_color {
                 write!(
-                    self.buffer,
+                    buffer,
                     "\x1b[38;2;{};{};{}m",
                     foreground.r, foreground.g, foreground.b,
                 )?
@@ -131,12 +158,144 @@ impl Painter {
                 if self.foreground_code != Some(code) {
                     self.foreground_code = Some(code);
 
-                    write!(self.buffer, "\x1b[38;5;{code}m")?
+                    write!(buffer, "\x1b[38;5;{code}m")?
                 }
             }
         }
 
-        self.buffer.write_all(char.as_bytes())?;
+        buffer.write_all(char.as_bytes())
+    }
+}
+
+pub struct Painter {
+    writer: Writer,
+    buffer: Vec<u8>,
+    state: PaintState,
+    true_color: bool,
+    filter: ColorFilter,
+}
+
+impl Painter {
+    pub fn new() -> Painter {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    /// Create a painter writing frames to `output` instead of the standard output
+    pub fn with_output(output: Box<dyn Write + Send>) -> Painter {
+        Painter {
+            writer: Writer::new(output),
+            buffer: Vec::new(),
+            state: PaintState::default(),
+            filter: ColorFilter::None,
+            true_color: matches!(
+                std::env::var("COLORTERM").unwrap_or_default().as_str(),
+                "truecolor" | "24bit"
+            ),
+        }
+    }
+
+    pub fn true_color(&self) -> bool {
+        self.true_color
+    }
+
+    pub fn set_true_color(&mut self, true_color: bool) {
+        self.true_color = true_color
+    }
+
+    pub fn color_filter(&self) -> ColorFilter {
+        self.filter
+    }
+
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        self.filter = filter
+    }
+
+    pub fn begin(&mut self) -> io::Result<()> {
+        write!(self.buffer, "\x1b[?25l\x1b[?12l")
+    }
+
+    pub fn end(&mut self, cursor: Option<(Point, CursorShape)>) -> io::Result<()> {
+        if let Some((cursor, shape)) = cursor {
+            write!(
+                self.buffer,
+                "\x1b[{};{}H\x1b[{} q\x1b[?25h\x1b[?12h",
+                cursor.y + 1,
+                cursor.x + 1,
+                shape.code()
+            )?;
+        }
+
+        self.buffer = self.writer.send(mem::take(&mut self.buffer));
+        self.state.cursor = None;
+
+        Ok(())
+    }
+
+    /// Write text to the system clipboard (OSC 52), along with the next frame
+    pub fn set_clipboard(&mut self, text: &str) -> io::Result<()> {
+        write!(
+            self.buffer,
+            "\x1b]52;c;{}\x07",
+            base64::encode(text.as_bytes())
+        )
+    }
+
+    /// Set the window and icon titles (OSC 0, 1 and 2), along with the next frame
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        write!(self.buffer, "\x1b]0;{title}\x07")?;
+        write!(self.buffer, "\x1b]1;{title}\x07")?;
+        write!(self.buffer, "\x1b]2;{title}\x07")
+    }
+
+    /// Ask the terminal for the clipboard content (OSC 52), along with the next frame.
+    ///
+    /// Many terminals deny reading the clipboard, and don't reply.
+    pub fn request_clipboard(&mut self) -> io::Result<()> {
+        write!(self.buffer, "\x1b]52;c;?\x07")
+    }
+
+    /// Whether the previous frame was handed to the terminal, see [`Writer`]
+    pub fn ready(&self) -> bool {
+        self.writer.ready()
+    }
+
+    /// Block until every frame was written to the terminal
+    pub fn flush(&self) {
+        self.writer.flush()
+    }
+
+    /// Encode consecutive bands of glyphs on worker threads.
+    ///
+    /// Each band starts from the state left by the previous ones, so the output
+    /// is the same as painting every glyph in order.
+    pub fn paint_bands(&mut self, bands: &[Vec<Glyph>]) -> io::Result<()> {
+        let true_color = self.true_color;
+        let mut state = self.state;
+        let mut jobs = Vec::with_capacity(bands.len());
+
+        for band in bands {
+            jobs.push((band, state));
+
+            if let Some(glyph) = band.last() {
+                state = state.after(glyph, true_color)
+            }
+        }
+
+        let buffers = parallel_map(jobs, |(band, mut state)| {
+            let mut buffer = Vec::new();
+
+            for glyph in band {
+                state.encode(glyph, true_color, &mut buffer)?
+            }
+
+            io::Result::Ok(buffer)
+        });
+
+        for buffer in buffers {
+            self.buffer.extend_from_slice(&buffer?)
+        }
+
+        self.state = state;
 
         Ok(())
     }
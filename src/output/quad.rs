@@ -18,23 +18,23 @@ pub fn binarize_quandrant(
     let max = a.max(b).max(c).max(d);
     let mid = min + (max - min) / 2.0;
 
-    // Step 3: average colors based on binary mask
+    // Step 3: average colors based on binary mask, in linear light
     match FourBits::new(a > mid, b > mid, c > mid, d > mid) {
-        B0000 => ("▄", x.avg_with(y), z.avg_with(w)),
-        B0001 => ("▖", x.avg_with(y).avg_with(z), w),
-        B0010 => ("▗", x.avg_with(y).avg_with(w), z),
-        B0011 => ("▄", x.avg_with(y), z.avg_with(w)),
-        B0100 => ("▝", x.avg_with(z).avg_with(w), y),
-        B0101 => ("▞", x.avg_with(z), y.avg_with(w)),
-        B0110 => ("▐", x.avg_with(w), y.avg_with(z)),
-        B0111 => ("▘", y.avg_with(z).avg_with(w), x),
-        B1000 => ("▘", y.avg_with(z).avg_with(w), x),
-        B1001 => ("▌", y.avg_with(z), x.avg_with(w)),
-        B1010 => ("▚", y.avg_with(w), x.avg_with(z)),
-        B1011 => ("▝", x.avg_with(z).avg_with(w), y),
-        B1100 => ("▄", x.avg_with(y), z.avg_with(w)),
-        B1101 => ("▗", x.avg_with(y).avg_with(w), z),
-        B1110 => ("▖", x.avg_with(y).avg_with(z), w),
-        B1111 => ("▄", x.avg_with(y), z.avg_with(w)),
+        B0000 => ("▄", Color::average([x, y]), Color::average([z, w])),
+        B0001 => ("▖", Color::average([x, y, z]), w),
+        B0010 => ("▗", Color::average([x, y, w]), z),
+        B0011 => ("▄", Color::average([x, y]), Color::average([z, w])),
+        B0100 => ("▝", Color::average([x, z, w]), y),
+        B0101 => ("▞", Color::average([x, z]), Color::average([y, w])),
+        B0110 => ("▐", Color::average([x, w]), Color::average([y, z])),
+        B0111 => ("▘", Color::average([y, z, w]), x),
+        B1000 => ("▘", Color::average([y, z, w]), x),
+        B1001 => ("▌", Color::average([y, z]), Color::average([x, w])),
+        B1010 => ("▚", Color::average([y, w]), Color::average([x, z])),
+        B1011 => ("▝", Color::average([x, z, w]), y),
+        B1100 => ("▄", Color::average([x, y]), Color::average([z, w])),
+        B1101 => ("▗", Color::average([x, y, w]), z),
+        B1110 => ("▖", Color::average([x, y, z]), w),
+        B1111 => ("▄", Color::average([x, y]), Color::average([z, w])),
     }
 }
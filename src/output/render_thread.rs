@@ -8,28 +8,69 @@ use crate::cli::CommandLine;
 
 use super::{FrameSync, Renderer};
 
+/// Maximum FPS while the terminal is not focused
+const UNFOCUSED_FPS: f32 = 2.0;
+
 /// Control a rendering thread that lazily starts.
 /// This allows the `Bridge` struct to be used in places
 /// where we do not expected the rendering thread to start.
 pub struct RenderThread {
-    thread: Option<(Sender<Message>, JoinHandle<()>)>,
+    sender: Sender<Message>,
+    /// Receiving end of the channel and renderer constructor, until the thread starts
+    boot: Option<(Receiver<Message>, RendererFactory)>,
+    thread: Option<JoinHandle<()>>,
     enabled: bool,
 }
 
+/// Send closures to a rendering thread from any thread, without locking.
+///
+/// Closures sent before the thread starts run once it does.
+#[derive(Clone)]
+pub struct RenderSender(Sender<Message>);
+
 type RenderClosure = Box<dyn FnMut(&mut Renderer) + Send>;
+type RendererFactory = Box<dyn FnOnce() -> Renderer + Send>;
 enum Message {
     Run(RenderClosure),
     Shutdown,
 }
 
+impl RenderSender {
+    /// Run a closure on the rendering thread.
+    pub fn render<F>(&self, run: F)
+    where
+        F: FnMut(&mut Renderer) + Send + 'static,
+    {
+        // The thread is gone after a shutdown, there's nothing left to render
+        self.0.send(Message::Run(Box::new(run))).ok();
+    }
+}
+
 impl RenderThread {
     pub fn new() -> Self {
+        Self::with_renderer(Renderer::new)
+    }
+
+    /// Create a rendering thread using a custom renderer, built when the thread starts.
+    pub fn with_renderer<F>(renderer: F) -> Self
+    where
+        F: FnOnce() -> Renderer + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
         Self {
+            sender,
+            boot: Some((receiver, Box::new(renderer))),
             thread: None,
             enabled: false,
         }
     }
 
+    /// Get a handle to send closures to the thread without holding this struct.
+    pub fn sender(&self) -> RenderSender {
+        RenderSender(self.sender.clone())
+    }
+
     /// Enable the rendering thread.
     /// Allows the thread to be lazily initiated.
     pub fn enable(&mut self) {
@@ -42,9 +83,7 @@ impl RenderThread {
         self.enabled = false;
         self.send(Message::Shutdown);
 
-        let (_, handle) = self.thread.take()?;
-
-        Some(handle)
+        self.thread.take()
     }
 
     /// Run a closure on the rendering thread.
@@ -56,16 +95,22 @@ impl RenderThread {
     }
 
     /// Boot the rendering thread, contains a simple event loop.
-    fn boot(rx: Receiver<Message>) {
+    fn boot(rx: Receiver<Message>, renderer: RendererFactory) {
         let cmd = CommandLine::parse();
         let mut sync = FrameSync::new(cmd.fps);
-        let mut renderer = Renderer::new();
+        let mut renderer = renderer();
+
+        renderer.set_color_filter(cmd.color_filter);
+        renderer.set_confirm_quit(cmd.confirm_quit);
+        renderer.set_widths(cmd.widths.clone());
         let mut needs_render = false;
+        let mut focused = renderer.focused();
 
         loop {
             // Get a deadline for the next frame
             let deadline = sync.deadline();
-            let mut wait = true;
+            // Don't block if a skipped frame needs to be rendered again
+            let mut wait = !needs_render;
 
             loop {
                 let message = if wait {
@@ -90,17 +135,28 @@ impl RenderThread {
                         closure(&mut renderer);
 
                         needs_render = true;
+
+                        // Throttle while unfocused, render right away when focused again
+                        if renderer.focused() != focused {
+                            focused = renderer.focused();
+
+                            sync.set_fps(match focused {
+                                true => cmd.fps,
+                                false => cmd.fps.min(UNFOCUSED_FPS),
+                            });
+
+                            break;
+                        }
                     }
                 }
             }
 
             // Render if needed
             if needs_render {
-                needs_render = false;
-
                 // Update the frame sync timings
                 sync.start();
-                renderer.render().unwrap();
+                // Try again on the next frame if the terminal is busy
+                needs_render = !renderer.render().unwrap();
             }
         }
     }
@@ -108,14 +164,17 @@ impl RenderThread {
     /// Send a message to the rendering thread.
     /// Creates a new thread if enabled and needed.
     fn send(&mut self, message: Message) {
-        if let Some((tx, _)) = &self.thread {
-            tx.send(message).unwrap()
-        } else if self.enabled {
-            let (tx, rx) = mpsc::channel();
-
-            tx.send(message).unwrap();
+        if self.thread.is_none() {
+            if !self.enabled {
+                return;
+            }
 
-            self.thread = Some((tx.clone(), thread::spawn(move || Self::boot(rx))));
+            if let Some((rx, renderer)) = self.boot.take() {
+                self.thread = Some(thread::spawn(move || Self::boot(rx, renderer)));
+            }
         }
+
+        // The thread is gone after a shutdown, there's nothing left to render
+        self.sender.send(message).ok();
     }
 }
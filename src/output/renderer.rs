@@ -1,43 +1,157 @@
-use std::{
-    io::{self, Write},
-    rc::Rc,
-};
+use std::io;
 
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
 use crate::{
     gfx::{Color, Point, Rect, Size},
     input::Key,
-    ui::navigation::{Navigation, NavigationAction},
+    ui::{
+        keymap::Keymap,
+        navigation::{Navigation, NavigationAction},
+    },
+    utils::parallel_map,
 };
 
-use super::{Cell, Grapheme, Painter};
+use super::{ColorFilter, CursorShape, Grapheme, Grid, Painter, Widths, average_rows};
+
+/// Maximum number of threads used to render a frame
+const MAX_THREADS: usize = 8;
+/// Number of cells below which a frame is rendered on a single thread
+const PARALLEL_CELLS: usize = 16 * 1024;
 
 pub struct Renderer {
     nav: Navigation,
-    cells: Vec<(Cell, Cell)>,
+    /// Terminal cells, the first row holds the navigation bar
+    grid: Grid,
     painter: Painter,
     size: Size,
+    /// Position of the focused text input caret in the page
+    caret: Option<(Point, CursorShape)>,
+    /// Size of a cell in pixels of the page bitmap
+    sampling: Size<f32>,
+    /// Number of threads used to render large frames
+    threads: usize,
+    /// Number of frames skipped while the terminal was busy
+    skipped: u64,
+    /// Whether the terminal window is focused
+    focused: bool,
+    /// Width of graphemes in the terminal
+    widths: Widths,
 }
 
 impl Renderer {
     pub fn new() -> Renderer {
+        Self::with_painter(Painter::new())
+    }
+
+    pub fn with_painter(painter: Painter) -> Renderer {
         Renderer {
             nav: Navigation::new(),
-            cells: Vec::with_capacity(0),
-            painter: Painter::new(),
+            grid: Grid::default(),
+            painter,
             size: Size::new(0, 0),
+            caret: None,
+            sampling: Size::new(2.0, 4.0),
+            threads: std::thread::available_parallelism()
+                .map(|threads| threads.get().min(MAX_THREADS))
+                .unwrap_or(1),
+            skipped: 0,
+            focused: true,
+            widths: Widths::default(),
         }
     }
 
+    /// Set the number of threads used to render large frames, 1 to render sequentially
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1)
+    }
+
+    /// Number of rows per band when splitting `rows` across worker threads
+    fn band_rows(&self, rows: usize, cells: usize) -> usize {
+        let threads = match cells {
+            0..PARALLEL_CELLS => 1,
+            _ => self.threads,
+        };
+
+        rows.div_ceil(threads).max(1)
+    }
+
     pub fn enable_true_color(&mut self) {
         self.painter.set_true_color(true)
     }
 
+    /// Whether the terminal window is focused, frames are throttled otherwise
+    pub fn focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused
+    }
+
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        self.painter.set_color_filter(filter);
+        self.grid.repaint();
+    }
+
     pub fn keypress(&mut self, key: &Key) -> io::Result<NavigationAction> {
         let action = self.nav.keypress(key);
 
+        self.apply(action)
+    }
+
+    /// Ask to press the quit shortcut twice
+    pub fn set_confirm_quit(&mut self, confirm: bool) {
+        self.nav.set_confirm_quit(confirm)
+    }
+
+    /// Set the default background color of the terminal, used by the navigation bar
+    pub fn set_terminal_background(&mut self, color: Color) {
+        self.nav.set_background(color)
+    }
+
+    /// Set the default text color of the terminal, used by the navigation bar
+    pub fn set_terminal_foreground(&mut self, color: Color) {
+        self.nav.set_foreground(color)
+    }
+
+    /// Measure graphemes the way the terminal does, for the page and the navigation bar
+    pub fn set_widths(&mut self, widths: Widths) {
+        self.nav.set_widths(widths.clone());
+        self.widths = widths;
+        self.grid.repaint();
+    }
+
+    /// Set the key chords bound to browser actions
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.nav.set_keymap(keymap)
+    }
+
+    /// Write text to the system clipboard
+    pub fn set_clipboard(&mut self, text: &str) -> io::Result<()> {
+        self.painter.set_clipboard(text)
+    }
+
+    /// Handle actions on the terminal output: clipboard and color filters
+    fn apply(&mut self, action: NavigationAction) -> io::Result<NavigationAction> {
+        match action {
+            NavigationAction::Copy(text) => self.set_clipboard(&text)?,
+            NavigationAction::ReadClipboard() => self.painter.request_clipboard()?,
+            NavigationAction::CycleColorFilter() => {
+                let filter = self.painter.color_filter().next();
+
+                tracing::debug!("switching to color filter {filter:?}");
+
+                self.set_color_filter(filter);
+            }
+            action => return Ok(action),
+        }
+
+        Ok(NavigationAction::Ignore)
+    }
+    pub fn paste(&mut self, text: &str) -> io::Result<NavigationAction> {
+        let action = self.nav.paste(text);
+
         Ok(action)
     }
     pub fn mouse_up(&mut self, origin: Point) -> io::Result<NavigationAction> {
@@ -60,83 +174,117 @@ impl Renderer {
         self.nav.push(url, can_go_back, can_go_forward)
     }
 
+    /// Set the cursor for the focused text input in the page, if any
+    pub fn set_caret(&mut self, caret: Option<(Point, CursorShape)>) {
+        self.caret = caret
+    }
+
     pub fn get_size(&self) -> Size {
         self.size
     }
 
-    pub fn set_size(&mut self, size: Size) {
+    /// Resize the terminal grid to `size` cells,
+    /// each cell covering `sampling` pixels of the page bitmap.
+    pub fn set_size(&mut self, size: Size, sampling: Size<f32>) {
+        self.sampling = sampling;
+
+        // Resizes can be notified by both the browser and the terminal
+        if size == self.size && self.grid.size().height > 0 {
+            return;
+        }
+
         self.nav.set_size(size);
         self.size = size;
+        // The terminal content is lost or garbled after a resize,
+        // a new grid is painted entirely.
+        self.grid = Grid::new(Size::new(size.width, size.height + 1));
+    }
 
-        let mut x = 0;
-        let mut y = 0;
-        let bound = size.width - 1;
-        let cells = (size.width + size.width * size.height) as usize;
+    /// Number of frames skipped because the terminal was still busy writing
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped
+    }
 
-        self.cells.clear();
-        self.cells.resize_with(cells, || {
-            let cell = (Cell::new(x, y), Cell::new(x, y));
+    /// Block until every rendered frame was written to the terminal
+    pub fn flush(&self) {
+        self.painter.flush()
+    }
 
-            if x < bound {
-                x += 1;
-            } else {
-                x = 0;
-                y += 1;
-            }
+    /// Render changes since the last frame, returns `false` if the frame was skipped.
+    ///
+    /// Frames are skipped while the terminal writer is behind,
+    /// their changes are painted along with the next rendered frame.
+    pub fn render(&mut self) -> io::Result<bool> {
+        if !self.painter.ready() {
+            self.skipped += 1;
 
-            cell
-        });
-    }
+            tracing::debug!("terminal busy, skipped frame ({} so far)", self.skipped);
+
+            return Ok(false);
+        }
 
-    pub fn render(&mut self) -> io::Result<()> {
         let size = self.size;
 
         for (origin, element) in self.nav.render(size) {
             self.fill_rect(
-                Rect::new(origin.x, origin.y, element.text.width() as u32, 1),
+                Rect::new(origin.x, origin.y, self.widths.str(&element.text) as u32, 1),
                 element.background,
             );
-            self.draw_text(
+            self.draw_run(
                 &element.text,
-                origin * (2, 1),
-                Size::splat(0),
+                origin,
+                Rect::new(0, 0, size.width, 1),
+                0,
                 element.foreground,
             );
         }
 
         self.painter.begin()?;
 
-        for (previous, current) in self.cells.iter_mut() {
-            if current == previous {
-                continue;
-            }
+        let rows = self.grid.size().height as usize;
+        let band = self.band_rows(rows, rows * size.width as usize);
+        let glyphs = self.grid.changes(band, self.painter.color_filter());
 
-            previous.quadrant = current.quadrant;
-            previous.grapheme = current.grapheme.clone();
+        self.painter.paint_bands(&glyphs)?;
 
-            self.painter.paint(current)?;
-        }
+        // The navigation bar takes precedence over the page when focused
+        let cursor = match self.nav.cursor() {
+            Some(cursor) => Some((cursor, CursorShape::Bar)),
+            None => self
+                .caret
+                .filter(|(caret, _)| caret.inside(self.page_rect())),
+        };
 
-        self.painter.end(self.nav.cursor())?;
+        self.painter.end(cursor)?;
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Area of the terminal displaying the page, below the navigation bar
+    fn page_rect(&self) -> Rect {
+        Rect::new(0, 1, self.size.width, self.size.height)
     }
 
     /// Draw the background from a pixel array encoded in RGBA8888
     pub fn draw_background(&mut self, pixels: &[u8], pixels_size: Size, rect: Rect) {
         let viewport = self.size.cast::<usize>();
+        let sampling = self.sampling;
+        let expected = (self.size.cast::<f32>() * sampling).floor().cast::<u32>();
 
-        if pixels.len() < viewport.width * viewport.height * 8 * 4 {
+        if pixels_size.width < expected.width
+            || pixels_size.height < expected.height
+            || pixels.len() < (pixels_size.width * pixels_size.height * 4) as usize
+        {
             tracing::debug!(
-                "unexpected size, actual: {}, expected: {}",
-                pixels.len(),
-                viewport.width * viewport.height * 8 * 4
+                "unexpected size, actual: {:?}, expected: {:?}",
+                pixels_size,
+                expected
             );
             return;
         }
 
-        let origin = rect.origin.cast::<f32>().max(0.0) / (2.0, 4.0);
-        let size = rect.size.cast::<f32>().max(0.0) / (2.0, 4.0);
+        let origin = rect.origin.cast::<f32>().max(0.0) / (sampling.width, sampling.height);
+        let size = rect.size.cast::<f32>().max(0.0) / sampling;
         let top = (origin.y.floor() as usize).min(viewport.height);
         let left = (origin.x.floor() as usize).min(viewport.width);
         let right = ((origin.x + size.width).ceil() as usize)
@@ -146,82 +294,105 @@ impl Renderer {
             .min(viewport.height)
             .max(top);
         let row_length = pixels_size.width as usize;
+        // Position of the pixel at a fraction of a cell, in pixels
+        let bound = pixels_size.cast::<usize>() - (1, 1);
+        let sample_x = |x: usize, fraction: f32| {
+            (((x as f32 + fraction) * sampling.width) as usize).min(bound.width)
+        };
+        let sample_y = |y: usize, fraction: f32| {
+            (((y as f32 + fraction) * sampling.height) as usize).min(bound.height)
+        };
         let pixel = |x, y| {
-            Color::new(
-                pixels[((x + y * row_length) * 4 + 2) as usize],
-                pixels[((x + y * row_length) * 4 + 1) as usize],
-                pixels[((x + y * row_length) * 4 + 0) as usize],
-            )
+            let index = (x + y * row_length) * 4;
+
+            Color::new(pixels[index + 2], pixels[index + 1], pixels[index])
         };
-        let pair = |x, y| pixel(x, y).avg_with(pixel(x, y + 1));
-
-        for y in top..bottom {
-            let index = (y + 1) * viewport.width;
-            let start = index + left;
-            let end = index + right;
-            let (mut x, y) = (left * 2, y * 4);
-
-            for (_, cell) in &mut self.cells[start..end] {
-                cell.quadrant = (
-                    pair(x + 0, y + 0),
-                    pair(x + 1, y + 0),
-                    pair(x + 1, y + 2),
-                    pair(x + 0, y + 2),
-                );
-
-                x += 2;
+        let pair = |x, (a, b)| Color::average([pixel(x, a), pixel(x, b)]);
+
+        let band = self.band_rows(bottom - top, (bottom - top) * (right - left));
+        // Downsample stripes of rows in parallel, the first row holds the navigation bar
+        let (rows, _) = self.grid.rows_mut(top + 1..bottom + 1);
+        let stripes = rows
+            .chunks_mut(band * viewport.width.max(1))
+            .enumerate()
+            .map(|(index, cells)| (top + index * band, cells))
+            .collect();
+
+        parallel_map(stripes, |(top, cells)| {
+            // Colors of the upper and lower halves of the cells of a row
+            let mut upper_colors = vec![Color::black(); (right - left) * 2];
+            let mut lower_colors = upper_colors.clone();
+
+            for (y, row) in (top..).zip(cells.chunks_mut(viewport.width)) {
+                let row = &mut row[left..right];
+
+                // Sample 2x4 pixels per cell, spread according to the cell aspect ratio
+                let upper = (sample_y(y, 0.0), sample_y(y, 0.25));
+                let lower = (sample_y(y, 0.5), sample_y(y, 0.75));
+
+                // Cells two pixels wide sample contiguous pixels, average them in bulk
+                if sampling.width == 2.0 {
+                    let span =
+                        |y: usize| &pixels[(y * row_length + left * 2) * 4..][..(right - left) * 8];
+
+                    average_rows(span(upper.0), span(upper.1), &mut upper_colors);
+                    average_rows(span(lower.0), span(lower.1), &mut lower_colors);
+
+                    for ((quadrant, upper), lower) in row
+                        .iter_mut()
+                        .zip(upper_colors.chunks_exact(2))
+                        .zip(lower_colors.chunks_exact(2))
+                    {
+                        *quadrant =
+                            [upper[0], upper[1], lower[1], lower[0]].map(|color| color.pack());
+                    }
+
+                    continue;
+                }
+
+                for (x, quadrant) in (left..right).zip(row) {
+                    let (left, right) = (sample_x(x, 0.0), sample_x(x, 0.5));
+
+                    *quadrant = [
+                        pair(left, upper),
+                        pair(right, upper),
+                        pair(right, lower),
+                        pair(left, lower),
+                    ]
+                    .map(|color| color.pack());
+                }
             }
-        }
+        });
     }
 
     pub fn clear_text(&mut self) {
-        for (_, cell) in self.cells.iter_mut() {
-            cell.grapheme = None
-        }
+        self.grid.clear_text()
     }
 
-    pub fn set_title(&self, title: &str) -> io::Result<()> {
-        let mut stdout = io::stdout();
-
-        write!(stdout, "\x1b]0;{title}\x07")?;
-        write!(stdout, "\x1b]1;{title}\x07")?;
-        write!(stdout, "\x1b]2;{title}\x07")?;
-
-        stdout.flush()
+    /// Set the terminal title, sent by the writer thread with the next frame
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        self.painter.set_title(title)
     }
 
     pub fn fill_rect(&mut self, rect: Rect, color: Color) {
-        self.draw(rect, |cell| {
-            cell.grapheme = None;
-            cell.quadrant = (color, color, color, color);
-        })
-    }
-
-    pub fn draw<F>(&mut self, bounds: Rect, mut draw: F)
-    where
-        F: FnMut(&mut Cell),
-    {
-        let origin = bounds.origin.cast::<usize>();
-        let size = bounds.size.cast::<usize>();
-        let viewport_width = self.size.width as usize;
-        let top = origin.y;
-        let bottom = top + size.height;
-
-        // Iterate over each row
-        for y in top..bottom {
-            let left = y * viewport_width + origin.x;
-            let right = left + size.width;
-
-            for (_, current) in self.cells[left..right].iter_mut() {
-                draw(current)
-            }
+        let origin = rect.origin.cast::<usize>();
+        let size = rect.size.cast::<usize>();
+        let width = self.size.width as usize;
+        let left = origin.x.min(width);
+        let right = (origin.x + size.width).min(width);
+        let (quadrants, text) = self.grid.rows_mut(origin.y..origin.y + size.height);
+
+        for (quadrants, text) in quadrants
+            .chunks_mut(width.max(1))
+            .zip(text.chunks_mut(width.max(1)))
+        {
+            quadrants[left..right].fill([color.pack(); 4]);
+            text[left..right].fill(None);
         }
     }
 
     /// Render some text into the terminal output
     pub fn draw_text(&mut self, string: &str, origin: Point, size: Size, color: Color) {
-        // Get an iterator starting at the text origin
-        let len = self.cells.len();
         let viewport = &self.size.cast::<usize>();
 
         if size.width > 2 && size.height > 2 {
@@ -232,52 +403,124 @@ impl Renderer {
             let top = (origin.y.max(0.0) as usize).min(viewport.height);
             let bottom = ((origin.y + size.height).max(0.0) as usize).min(viewport.height);
 
-            for y in top..bottom {
-                let index = y * viewport.width;
-                let start = index + left;
-                let end = index + right;
+            let (_, text) = self.grid.rows_mut(top..bottom);
 
-                for (_, cell) in self.cells[start..end].iter_mut() {
-                    cell.grapheme = None
-                }
+            for row in text.chunks_mut(viewport.width.max(1)) {
+                row[left..right].fill(None)
             }
         } else {
-            // Compute the buffer index based on the position
-            let index = origin.x / 2 + (origin.y + 1) / 4 * (viewport.width as i32);
-            let mut iter = self.cells[len.min(index as usize)..].iter_mut();
-
-            // Get every Unicode grapheme in the input string
-            for grapheme in UnicodeSegmentation::graphemes(string, true) {
-                let width = grapheme.width();
-
-                for index in 0..width {
-                    // Get the next terminal cell at the given position
-                    match iter.next() {
-                        // Stop if we're at the end of the buffer
-                        None => return,
-                        // Set the cell to the current grapheme
-                        Some((_, cell)) => {
-                            let next = Grapheme {
-                                // Create a new shared reference to the text
-                                color,
-                                index,
-                                width,
-                                // Export the set of unicode code points for this graphene into an UTF-8 string
-                                char: grapheme.to_string(),
-                            };
-
-                            if match cell.grapheme {
-                                None => true,
-                                Some(ref previous) => {
-                                    previous.color != next.color || previous.char != next.char
-                                }
-                            } {
-                                cell.grapheme = Some(Rc::new(next))
-                            }
-                        }
-                    }
-                }
+            // Text runs are positioned on their baseline
+            let baseline = origin.y + 1;
+            let cursor = Point::new(origin.x.div_euclid(2), baseline.div_euclid(4));
+            // Distance between the run baseline and the middle of the cell, from 1 to 3 pixels
+            let offset = (baseline.rem_euclid(4) * 2 - 3).unsigned_abs() as u8;
+            // Clip to the page viewport below the navigation bar,
+            // and to the run's own width if it has one.
+            let mut clip = self.page_rect();
+
+            if size.width > 0 {
+                let right = (origin.x + size.width as i32 + 1).div_euclid(2);
+
+                clip.size.width = right.clamp(0, clip.size.width as i32) as u32;
             }
+
+            self.draw_run(string, cursor, clip, offset, color)
+        }
+    }
+
+    /// Draw a line of text starting at the `origin` cell, clipped to the `clip` rect.
+    ///
+    /// Runs overflowing the right edge of `clip` are truncated with an ellipsis.
+    /// A cell already holding text is only overwritten by a grapheme with a closer
+    /// baseline (lower `offset`), the last drawn run wins ties.
+    fn draw_run(&mut self, string: &str, origin: Point, clip: Rect, offset: u8, color: Color) {
+        let rows = self.grid.size().height as i32;
+        let top = clip.origin.y.max(0);
+        let bottom = (clip.origin.y + clip.size.height as i32).min(rows);
+
+        if origin.y < top || origin.y >= bottom {
+            return;
+        }
+
+        let left = clip.origin.x.max(0);
+        let mut right = (clip.origin.x + clip.size.width as i32).min(self.size.width as i32);
+
+        if origin.x >= right {
+            return;
+        }
+
+        let graphemes = string
+            .graphemes(true)
+            .map(|grapheme| (grapheme, self.widths.grapheme(grapheme) as i32))
+            .filter(|&(_, width)| width > 0)
+            .collect::<Vec<_>>();
+        let truncated = origin.x + graphemes.iter().map(|(_, width)| width).sum::<i32>() > right;
+
+        // Keep the last visible cell for the ellipsis
+        if truncated {
+            right -= 1;
+        }
+
+        let mut x = origin.x;
+
+        for (grapheme, width) in graphemes {
+            if x + width > right {
+                break;
+            }
+
+            if x >= left {
+                self.put_grapheme(Point::new(x, origin.y), grapheme, width, offset, color);
+            }
+
+            x += width;
+        }
+
+        if truncated && right >= left.max(origin.x) {
+            // The ellipsis has an ambiguous width, wide in CJK terminals
+            let ellipsis = match self.widths.grapheme("…") {
+                1 => "…",
+                _ => ".",
+            };
+
+            self.put_grapheme(Point::new(right, origin.y), ellipsis, 1, offset, color);
+        }
+    }
+
+    /// Set the grapheme of the cells starting at `cursor`, unless they're owned by a closer run.
+    fn put_grapheme(&mut self, cursor: Point, char: &str, width: i32, offset: u8, color: Color) {
+        let row = cursor.y as usize;
+        let start = cursor.x as usize;
+        let end = start + width as usize;
+        let text = &self.grid.text()[row * self.size.width as usize..];
+
+        if text[start..end]
+            .iter()
+            .any(|cell| matches!(cell, Some(previous) if previous.offset < offset))
+        {
+            return;
+        }
+
+        let char = self.grid.intern(char);
+        let (_, text) = self.grid.rows_mut(row..row + 1);
+
+        // Don't leave halves of a wide grapheme we partially overwrite
+        for index in start..end {
+            if let Some(previous) = text[index].take() {
+                let first = index - previous.index as usize;
+                let last = (first + previous.width as usize).min(text.len());
+
+                text[first..last].fill(None)
+            }
+        }
+
+        for (index, cell) in text[start..end].iter_mut().enumerate() {
+            *cell = Some(Grapheme {
+                char,
+                color: color.pack(),
+                index: index as u8,
+                width: width as u8,
+                offset,
+            })
         }
     }
 }
@@ -0,0 +1,215 @@
+use std::{fmt, str::FromStr};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Kind of grapheme whose width terminals disagree on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphemeKind {
+    /// Single emoji character, such as 😀
+    Emoji,
+    /// Emoji with a skin tone modifier, such as 👍🏽
+    Modifier,
+    /// Text character with an emoji variation selector, such as ❤️
+    Presentation,
+    /// Emoji joined with zero width joiners, such as 👩‍💻
+    Sequence,
+    /// East Asian ambiguous width character, such as ①
+    Ambiguous,
+}
+
+impl GraphemeKind {
+    pub const ALL: [GraphemeKind; 5] = [
+        GraphemeKind::Emoji,
+        GraphemeKind::Modifier,
+        GraphemeKind::Presentation,
+        GraphemeKind::Sequence,
+        GraphemeKind::Ambiguous,
+    ];
+
+    /// Grapheme printed to measure the width of this kind
+    pub fn sample(&self) -> &'static str {
+        match self {
+            GraphemeKind::Emoji => "\u{1f600}",
+            GraphemeKind::Modifier => "\u{1f44d}\u{1f3fd}",
+            GraphemeKind::Presentation => "\u{2764}\u{fe0f}",
+            GraphemeKind::Sequence => "\u{1f469}\u{200d}\u{1f4bb}",
+            GraphemeKind::Ambiguous => "\u{2460}",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            GraphemeKind::Emoji => "emoji",
+            GraphemeKind::Modifier => "modifier",
+            GraphemeKind::Presentation => "presentation",
+            GraphemeKind::Sequence => "sequence",
+            GraphemeKind::Ambiguous => "ambiguous",
+        }
+    }
+
+    /// Kind of a grapheme, if it's one terminals disagree on
+    pub fn of(grapheme: &str) -> Option<GraphemeKind> {
+        let mut chars = grapheme.chars();
+        let first = chars.next()?;
+
+        if chars.as_str().is_empty() {
+            return match first {
+                '\u{1f300}'..='\u{1faff}' if first.width() == Some(2) => Some(GraphemeKind::Emoji),
+                _ if grapheme.width() != grapheme.width_cjk() => Some(GraphemeKind::Ambiguous),
+                _ => None,
+            };
+        }
+
+        if chars.clone().any(|char| char == '\u{200d}') {
+            Some(GraphemeKind::Sequence)
+        } else if chars.clone().any(|char| char == '\u{fe0f}') {
+            Some(GraphemeKind::Presentation)
+        } else if chars.any(|char| ('\u{1f3fb}'..='\u{1f3ff}').contains(&char)) {
+            Some(GraphemeKind::Modifier)
+        } else if grapheme.width() != grapheme.width_cjk() {
+            Some(GraphemeKind::Ambiguous)
+        } else {
+            None
+        }
+    }
+}
+
+/// How a terminal measures a kind of grapheme, when it's not as a single cluster
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measure {
+    /// Sum of the widths of each character
+    Chars,
+    /// Ambiguous characters are wide, as in CJK locales
+    Cjk,
+    /// A single cell
+    Narrow,
+}
+
+impl Measure {
+    const ALL: [Measure; 3] = [Measure::Chars, Measure::Cjk, Measure::Narrow];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Measure::Chars => "chars",
+            Measure::Cjk => "cjk",
+            Measure::Narrow => "narrow",
+        }
+    }
+
+    fn width(&self, grapheme: &str) -> usize {
+        match self {
+            Measure::Chars => grapheme.chars().filter_map(|char| char.width()).sum(),
+            Measure::Cjk => grapheme.width_cjk(),
+            Measure::Narrow => 1,
+        }
+    }
+}
+
+/// Width of graphemes in terminal cells, following `unicode-width` unless
+/// the terminal was found to measure some kinds of graphemes differently.
+///
+/// Overrides are written as a comma separated list of `kind=measure`,
+/// such as `sequence=chars,ambiguous=cjk`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Widths {
+    overrides: Vec<(GraphemeKind, Measure)>,
+}
+
+impl Widths {
+    /// Build the table from the widths measured by the terminal for the sample of each kind
+    pub fn from_probe(measured: &[(GraphemeKind, usize)]) -> Widths {
+        let mut overrides = Vec::new();
+
+        for &(kind, width) in measured {
+            let sample = kind.sample();
+
+            if sample.width() == width {
+                continue;
+            }
+
+            match Measure::ALL
+                .into_iter()
+                .find(|measure| measure.width(sample) == width)
+            {
+                Some(measure) => overrides.push((kind, measure)),
+                None => tracing::warn!("unexpected width for {sample:?}: {width}"),
+            }
+        }
+
+        Widths { overrides }
+    }
+
+    /// Whether the terminal measures graphemes like `unicode-width`
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Width of a grapheme cluster
+    pub fn grapheme(&self, grapheme: &str) -> usize {
+        let measure = match GraphemeKind::of(grapheme) {
+            Some(kind) if !self.is_empty() => self
+                .overrides
+                .iter()
+                .find(|&&(overridden, _)| overridden == kind)
+                .map(|&(_, measure)| measure),
+            _ => None,
+        };
+
+        match measure {
+            Some(measure) => measure.width(grapheme),
+            None => grapheme.width(),
+        }
+    }
+
+    /// Width of a string
+    pub fn str(&self, string: &str) -> usize {
+        match self.is_empty() {
+            true => string.width(),
+            false => string
+                .graphemes(true)
+                .map(|grapheme| self.grapheme(grapheme))
+                .sum(),
+        }
+    }
+}
+
+impl fmt::Display for Widths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (kind, measure)) in self.overrides.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?
+            }
+
+            write!(f, "{}={}", kind.name(), measure.name())?
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Widths {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut overrides = Vec::new();
+
+        for item in text.split(',').filter(|item| !item.is_empty()) {
+            let (kind, measure) = item
+                .split_once('=')
+                .ok_or_else(|| format!("expected `kind=measure`, got {item:?}"))?;
+            let kind = GraphemeKind::ALL
+                .into_iter()
+                .find(|known| known.name() == kind)
+                .ok_or_else(|| format!("unknown grapheme kind: {kind}"))?;
+            let measure = Measure::ALL
+                .into_iter()
+                .find(|known| known.name() == measure)
+                .ok_or_else(|| format!("unknown measure: {measure}"))?;
+
+            overrides.push((kind, measure))
+        }
+
+        Ok(Widths { overrides })
+    }
+}
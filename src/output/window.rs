@@ -10,6 +10,8 @@ pub struct Window {
     pub dpi: f32,
     /// Size of a terminal cell in pixels
     pub scale: Size<f32>,
+    /// Size of a terminal cell in device pixels
+    pub sampling: Size<f32>,
     /// Size of the termina window in cells
     pub cells: Size,
     /// Size of the browser window in pixels
@@ -22,8 +24,9 @@ impl Window {
     /// Read the window
     pub fn read() -> Window {
         let mut window = Self {
-            dpi: 1.0,
+            dpi: 0.0,
             scale: (0.0, 0.0).into(),
+            sampling: (0.0, 0.0).into(),
             cells: (0, 0).into(),
             browser: (0, 0).into(),
             cmd: CommandLine::parse(),
@@ -35,7 +38,7 @@ impl Window {
     }
 
     pub fn update(&mut self) -> &Self {
-        let (mut term, mut cell) = unsafe {
+        let (mut term, pixels) = unsafe {
             let mut ptr = MaybeUninit::<libc::winsize>::uninit();
 
             if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, ptr.as_mut_ptr()) == 0 {
@@ -50,11 +53,6 @@ impl Window {
             }
         };
 
-        if cell.width == 0 || cell.height == 0 {
-            cell.width = 8;
-            cell.height = 16;
-        }
-
         if term.width == 0 || term.height == 0 {
             let cols = match parse_var("COLUMNS").unwrap_or(0) {
                 0 => 80,
@@ -77,21 +75,24 @@ impl Window {
             term.height = rows;
         }
 
-        let zoom = 1.5 * self.cmd.zoom;
-        let cells = Size::new(term.width.max(1), term.height.max(2) - 1);
-        let auto_scale = false;
-        let cell_pixels = if auto_scale {
-            Size::new(cell.width as f32, cell.height as f32) / cells.cast()
+        let cell_pixels = if pixels.width > 0 && pixels.height > 0 {
+            pixels.cast::<f32>() / term.cast::<f32>()
         } else {
-            Size::new(8.0, 16.0)
+            // Queried by the parent process, replies would be stolen from the browser
+            self.cmd.cell_pixels.unwrap_or(Size::new(8.0, 16.0))
         };
-        // Normalize the cells dimensions for an aspect ratio of 1:2
-        let cell_width = (cell_pixels.width + cell_pixels.height / 2.0) / 2.0;
+        let zoom = 1.5 * self.cmd.zoom;
+
+        // The browser reads the DPI once on startup, keep it stable
+        if self.dpi == 0.0 {
+            // Round DPI to 2 decimals for proper viewport computations
+            self.dpi = (2.0 / cell_pixels.width * zoom * 100.0).ceil() / 100.0;
+        }
 
-        // Round DPI to 2 decimals for proper viewport computations
-        self.dpi = (2.0 / cell_width * zoom * 100.0).ceil() / 100.0;
-        // A virtual cell should contain a 2x4 pixel quadrant
-        self.scale = Size::new(2.0, 4.0) / self.dpi;
+        // A virtual cell should contain a 2x4 pixel quadrant,
+        // stretched vertically if the cell aspect ratio is not 1:2.
+        self.sampling = Size::new(2.0, 2.0 * cell_pixels.height / cell_pixels.width);
+        self.scale = self.sampling / self.dpi;
         // Keep some space for the UI
         self.cells = Size::new(term.width.max(1), term.height.max(2) - 1).cast();
         self.browser = self.cells.cast::<f32>().mul(self.scale).ceil().cast();
@@ -0,0 +1,129 @@
+use std::{
+    io::Write,
+    mem,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+};
+
+/// Write frames to the terminal from a dedicated thread,
+/// so that a slow terminal or connection doesn't block rendering.
+///
+/// At most one frame waits while another is being written,
+/// callers should skip frames until the writer is `ready`.
+pub struct Writer {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when a frame is sent, written, or the writer is closed
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// Frame waiting to be written
+    frame: Option<Vec<u8>>,
+    /// Whether a frame is being written
+    writing: bool,
+    /// Buffer of the last written frame, reused for the next one
+    spare: Vec<u8>,
+    closed: bool,
+}
+
+impl Writer {
+    pub fn new(output: Box<dyn Write + Send>) -> Writer {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+
+            thread::spawn(move || Self::run(shared, output))
+        };
+
+        Writer {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Whether a new frame can be sent without waiting
+    pub fn ready(&self) -> bool {
+        self.state().frame.is_none()
+    }
+
+    /// Send a frame to be written, returns an empty buffer for the next one.
+    ///
+    /// A frame still waiting to be written is replaced,
+    /// check that the writer is `ready` first.
+    pub fn send(&self, frame: Vec<u8>) -> Vec<u8> {
+        let mut state = self.state();
+
+        state.frame = Some(frame);
+        self.shared.changed.notify_all();
+
+        mem::take(&mut state.spare)
+    }
+
+    /// Block until every frame sent was written
+    pub fn flush(&self) {
+        let mut state = self.state();
+
+        while state.frame.is_some() || state.writing {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    fn run(shared: Arc<Shared>, mut output: Box<dyn Write + Send>) {
+        loop {
+            let mut frame = {
+                let mut state = shared.state.lock().unwrap();
+
+                loop {
+                    if let Some(frame) = state.frame.take() {
+                        state.writing = true;
+
+                        break frame;
+                    }
+
+                    // Pending frames are written before closing
+                    if state.closed {
+                        return;
+                    }
+
+                    state = shared.changed.wait(state).unwrap();
+                }
+            };
+
+            if let Err(error) = output.write_all(&frame).and_then(|_| output.flush()) {
+                tracing::error!("failed to write frame: {error}");
+            }
+
+            frame.clear();
+
+            let mut state = shared.state.lock().unwrap();
+
+            state.writing = false;
+            state.spare = frame;
+            shared.changed.notify_all();
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.state().closed = true;
+        self.shared.changed.notify_all();
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
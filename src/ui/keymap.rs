@@ -0,0 +1,360 @@
+use std::{env, fmt, fs, io, path::PathBuf, str::FromStr};
+
+use crate::input::{Key, KeyCode, KeyModifiers, NamedKey};
+
+/// Browser action bound to a key chord
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Back,
+    Forward,
+    Reload,
+    FocusUrl,
+    Quit,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    Find,
+    Copy,
+    Paste,
+    ColorFilter,
+}
+
+impl Action {
+    const ALL: [Action; 12] = [
+        Action::Back,
+        Action::Forward,
+        Action::Reload,
+        Action::FocusUrl,
+        Action::Quit,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ZoomReset,
+        Action::Find,
+        Action::Copy,
+        Action::Paste,
+        Action::ColorFilter,
+    ];
+
+    /// Name used in the keymap file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::Reload => "reload",
+            Action::FocusUrl => "focus-url",
+            Action::Quit => "quit",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::ZoomReset => "zoom-reset",
+            Action::Find => "find",
+            Action::Copy => "copy",
+            Action::Paste => "paste",
+            Action::ColorFilter => "color-filter",
+        }
+    }
+
+    /// Chords bound to the action by default
+    fn defaults(&self) -> &'static [&'static str] {
+        // Option+arrows move by words in macOS terminals
+        let macos = env::consts::OS == "macos";
+
+        match self {
+            Action::Back if macos => &["meta+left"],
+            Action::Back => &["alt+left"],
+            Action::Forward if macos => &["meta+right"],
+            Action::Forward => &["alt+right"],
+            Action::Reload => &["f5", "ctrl+r"],
+            Action::FocusUrl => &["ctrl+l", "f6"],
+            Action::Quit => &["ctrl+q"],
+            // Without the kitty keyboard protocol, Ctrl+= and Ctrl+0 are sent as
+            // plain characters, and Ctrl+- as Ctrl+_. Alt chords work everywhere.
+            Action::ZoomIn => &["ctrl+=", "ctrl++", "alt+="],
+            Action::ZoomOut => &["ctrl+-", "ctrl+_", "alt+-"],
+            Action::ZoomReset => &["ctrl+0", "alt+0"],
+            Action::Find => &["ctrl+f"],
+            Action::Copy => &["ctrl+shift+c"],
+            Action::Paste => &["ctrl+shift+v"],
+            Action::ColorFilter => &["ctrl+\\"],
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.name() == name)
+            .ok_or_else(|| format!("unknown action: {name}"))
+    }
+}
+
+/// Key with Ctrl, Alt, Shift or Meta, other modifiers are ignored.
+///
+/// Letters are lowercase with Shift, other characters are matched
+/// as typed, whether Shift was needed or not.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Chord {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Chord {
+        let mut modifiers = KeyModifiers {
+            control: modifiers.control,
+            alt: modifiers.alt,
+            shift: modifiers.shift,
+            meta: modifiers.meta,
+            ..Default::default()
+        };
+        let code = match code {
+            KeyCode::Char(char) if char.is_uppercase() => {
+                modifiers.shift = true;
+
+                KeyCode::Char(char.to_lowercase().next().unwrap_or(char))
+            }
+            KeyCode::Char(char) if !char.is_alphabetic() => {
+                modifiers.shift = false;
+
+                KeyCode::Char(char)
+            }
+            code => code,
+        };
+
+        Chord { code, modifiers }
+    }
+}
+
+impl From<&Key> for Chord {
+    fn from(key: &Key) -> Self {
+        Chord::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    /// Parse a chord such as `ctrl+shift+c`, `alt+left` or `f5`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lowercase = text.trim().to_lowercase();
+        let (modifiers, key) = match lowercase.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => lowercase.rsplit_once('+').unwrap_or(("", &lowercase)),
+        };
+        let mut parsed = KeyModifiers::default();
+
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier {
+                "ctrl" | "control" => parsed.control = true,
+                "alt" | "option" => parsed.alt = true,
+                "shift" => parsed.shift = true,
+                "meta" | "super" | "cmd" => parsed.meta = true,
+                _ => return Err(format!("unknown modifier in {text:?}: {modifier}")),
+            }
+        }
+
+        let named = match key {
+            "enter" | "return" => NamedKey::Enter,
+            "tab" => NamedKey::Tab,
+            "backspace" => NamedKey::Backspace,
+            "esc" | "escape" => NamedKey::Escape,
+            "up" => NamedKey::Up,
+            "down" => NamedKey::Down,
+            "left" => NamedKey::Left,
+            "right" => NamedKey::Right,
+            "home" => NamedKey::Home,
+            "end" => NamedKey::End,
+            "insert" => NamedKey::Insert,
+            "delete" => NamedKey::Delete,
+            "pageup" => NamedKey::PageUp,
+            "pagedown" => NamedKey::PageDown,
+            "space" => return Ok(Chord::new(KeyCode::Char(' '), parsed)),
+            key => match key.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(number @ 1..=35)) => NamedKey::Function(number),
+                _ => {
+                    let mut chars = key.chars();
+
+                    return match (chars.next(), chars.next()) {
+                        (Some(char), None) => Ok(Chord::new(KeyCode::Char(char), parsed)),
+                        _ => Err(format!("unknown key in {text:?}: {key}")),
+                    };
+                }
+            },
+        };
+
+        Ok(Chord::new(KeyCode::Named(named), parsed))
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = &self.modifiers;
+
+        for (set, name) in [
+            (modifiers.control, "ctrl+"),
+            (modifiers.alt, "alt+"),
+            (modifiers.shift, "shift+"),
+            (modifiers.meta, "meta+"),
+        ] {
+            if set {
+                f.write_str(name)?
+            }
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(char) => write!(f, "{char}"),
+            KeyCode::Named(NamedKey::Function(number)) => write!(f, "f{number}"),
+            KeyCode::Named(named) => write!(f, "{}", format!("{named:?}").to_lowercase()),
+        }
+    }
+}
+
+/// Key chords bound to browser actions.
+///
+/// Defaults can be overridden from a keymap file, with one action per line
+/// followed by its chords, or `none` to unbind it:
+///
+/// ```text
+/// # Comments start with a hash
+/// reload = f5, ctrl+r
+/// find = none
+/// ```
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Chord, Action)>,
+    /// Invalid lines and conflicting chords, reported at startup
+    errors: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .flat_map(|action| {
+                action
+                    .defaults()
+                    .iter()
+                    .map(move |chord| (chord.parse().unwrap(), action))
+            })
+            .collect();
+
+        Keymap {
+            bindings,
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Default location of the keymap file
+    pub fn default_path() -> Option<PathBuf> {
+        let config = match env::var_os("XDG_CONFIG_HOME") {
+            Some(config) => PathBuf::from(config),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config.join("carbonyl").join("keymap.conf"))
+    }
+
+    /// Load the keymap file at `path`, or the default keymap if there's none.
+    ///
+    /// A missing file is only an error if `path` was given explicitly.
+    pub fn load(path: Option<PathBuf>) -> Keymap {
+        let (path, explicit) = match path {
+            Some(path) => (Some(path), true),
+            None => (Self::default_path(), false),
+        };
+        let Some(path) = path else {
+            return Keymap::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(config) => Self::parse(&config),
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !explicit => Keymap::default(),
+            Err(error) => Keymap {
+                errors: vec![format!("failed to read {}: {error}", path.display())],
+                ..Keymap::default()
+            },
+        }
+    }
+
+    /// Apply the bindings of a keymap file to the defaults
+    pub fn parse(config: &str) -> Keymap {
+        let mut keymap = Keymap::default();
+
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Err(error) = keymap.bind(line) {
+                keymap.errors.push(format!("line {}: {error}", index + 1))
+            }
+        }
+
+        keymap.errors.extend(keymap.conflicts());
+        keymap
+    }
+
+    /// Replace the chords of an action from a `action = chord, ...` line
+    fn bind(&mut self, line: &str) -> Result<(), String> {
+        let (action, chords) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected `action = chords`, got {line:?}"))?;
+        let action = action.trim().parse::<Action>()?;
+        let chords = match chords.trim() {
+            "none" => Vec::new(),
+            chords => chords
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<Chord>, _>>()?,
+        };
+
+        self.bindings.retain(|&(_, bound)| bound != action);
+        self.bindings
+            .extend(chords.into_iter().map(|chord| (chord, action)));
+
+        Ok(())
+    }
+
+    /// Chords bound to more than one action, only the first one is used
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        for (index, (chord, action)) in self.bindings.iter().enumerate() {
+            if let Some((_, first)) = self.bindings[..index]
+                .iter()
+                .find(|(previous, bound)| previous == chord && bound != action)
+            {
+                conflicts.push(format!(
+                    "{chord} is bound to both {} and {}, using {}",
+                    first.name(),
+                    action.name(),
+                    first.name()
+                ))
+            }
+        }
+
+        conflicts
+    }
+
+    /// Problems found while loading the keymap
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Action bound to a key, if any
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        let chord = Chord::from(key);
+
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == chord)
+            .map(|&(_, action)| action)
+    }
+}
@@ -1,12 +1,13 @@
-use std::env;
-
-use unicode_width::UnicodeWidthStr;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     gfx::{Color, Point, Size},
-    input::Key,
+    input::{Key, KeyAction, KeyCode, NamedKey},
+    output::Widths,
 };
 
+use super::keymap::{Action, Chord, Keymap};
+
 pub enum NavigationAction {
     Ignore,
     Forward,
@@ -14,6 +15,39 @@ pub enum NavigationAction {
     GoBack(),
     GoForward(),
     Refresh(),
+    /// Write text to the clipboard
+    Copy(String),
+    /// Copy the page selection to the clipboard
+    CopySelection(),
+    /// Read the clipboard, and paste it
+    ReadClipboard(),
+    /// Shut the browser down
+    Quit(),
+    ZoomIn(),
+    ZoomOut(),
+    ZoomReset(),
+    /// Find in the page
+    Find(),
+    /// Switch to the next color filter
+    CycleColorFilter(),
+}
+
+/// Whether the terminal uses dark text on a light background, or the opposite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    /// Pick the scheme matching a background color
+    pub fn from_background(background: Color) -> ColorScheme {
+        // Luminance of a mid-gray, as perceived
+        match background.luminance() < 0.18 {
+            true => ColorScheme::Dark,
+            false => ColorScheme::Light,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -29,6 +63,16 @@ pub struct Navigation {
     cursor: Option<usize>,
     can_go_back: bool,
     can_go_forward: bool,
+    keymap: Keymap,
+    /// Whether the quit shortcut must be pressed twice
+    confirm_quit: bool,
+    /// Message asking to confirm, shown in place of the URL
+    quit_prompt: Option<String>,
+    /// Default colors of the terminal, if reported
+    background: Option<Color>,
+    foreground: Option<Color>,
+    /// Width of graphemes in the terminal
+    widths: Widths,
 }
 
 impl Navigation {
@@ -39,51 +83,130 @@ impl Navigation {
             cursor: None,
             can_go_back: false,
             can_go_forward: false,
+            keymap: Keymap::default(),
+            confirm_quit: false,
+            quit_prompt: None,
+            background: None,
+            foreground: None,
+            widths: Widths::default(),
         }
     }
 
+    /// Ask to press the quit shortcut twice, to avoid quitting by mistake
+    pub fn set_confirm_quit(&mut self, confirm: bool) {
+        self.confirm_quit = confirm
+    }
+
+    /// Use the default colors of the terminal for the navigation bar
+    pub fn set_background(&mut self, color: Color) {
+        self.background = Some(color)
+    }
+
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = Some(color)
+    }
+
+    /// Background, foreground and disabled colors of the navigation bar
+    fn colors(&self) -> (Color, Color, Color) {
+        let background = self.background.unwrap_or(Color::splat(255));
+        let foreground =
+            self.foreground
+                .unwrap_or_else(|| match ColorScheme::from_background(background) {
+                    ColorScheme::Light => Color::splat(0),
+                    ColorScheme::Dark => Color::splat(255),
+                });
+
+        (
+            background,
+            foreground,
+            Color::average([background, background, foreground]),
+        )
+    }
+
+    pub fn set_widths(&mut self, widths: Widths) {
+        self.widths = widths
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap
+    }
+
     pub fn cursor(&self) -> Option<Point> {
-        Some((11 + self.cursor? as i32, 0).into())
+        if self.quit_prompt.is_some() {
+            return None;
+        }
+
+        let cursor = self.cursor?;
+        let url = self.url.as_deref().unwrap_or_default();
+
+        Some(
+            (
+                11 + self.widths.str(&url[..byte_offset(url, cursor)]) as i32,
+                0,
+            )
+                .into(),
+        )
     }
 
     pub fn keypress(&mut self, key: &Key) -> NavigationAction {
-        let modifier_key = match env::consts::OS {
-            "macos" => key.modifiers.meta,
-            _ => key.modifiers.alt,
-        };
+        let action = self.keymap.action(key);
+        // Any other key cancels quitting
+        let prompt = self.quit_prompt.take();
+
+        match action {
+            // Keeping the shortcut pressed doesn't confirm
+            Some(Action::Quit) if key.action == KeyAction::Repeat => {
+                self.quit_prompt = prompt;
+
+                return NavigationAction::Ignore;
+            }
+            Some(Action::Quit) if self.confirm_quit && prompt.is_none() => {
+                self.quit_prompt = Some(format!("Press {} again to quit", Chord::from(key)));
+
+                return NavigationAction::Ignore;
+            }
+            Some(action) => return self.action(action),
+            None => (),
+        }
 
         match self.cursor {
-            None => match (modifier_key, key.char) {
-                (true, 0x14) => NavigationAction::GoBack(),
-                (true, 0x13) => NavigationAction::GoForward(),
-                _ => NavigationAction::Forward,
-            },
+            None => NavigationAction::Forward,
             Some(cursor) => {
                 if let Some(url) = &mut self.url {
-                    // TODO: Unicode
-                    match key.char {
-                        // Return
-                        0x0d => return NavigationAction::GoTo(url.clone()),
-                        // Up
-                        0x11 => self.cursor = Some(0),
-                        // Down
-                        0x12 => self.cursor = Some(url.width()),
-                        // Right
-                        0x13 => self.cursor = Some((cursor + 1).min(url.width())),
-                        // Left
-                        0x14 => self.cursor = Some(if cursor > 0 { cursor - 1 } else { 0 }),
-                        // Backspace
-                        0x7f => {
+                    let len = url.chars().count();
+                    let cursor = cursor.min(len);
+
+                    match key.code {
+                        KeyCode::Named(NamedKey::Enter) => {
+                            return NavigationAction::GoTo(url.clone());
+                        }
+                        KeyCode::Named(NamedKey::Up | NamedKey::Home) => self.cursor = Some(0),
+                        KeyCode::Named(NamedKey::Down | NamedKey::End) => self.cursor = Some(len),
+                        KeyCode::Named(NamedKey::Right) => {
+                            self.cursor = Some((cursor + 1).min(len))
+                        }
+                        KeyCode::Named(NamedKey::Left) => {
+                            self.cursor = Some(cursor.saturating_sub(1))
+                        }
+                        KeyCode::Named(NamedKey::Delete) => {
+                            if cursor < len {
+                                url.remove(byte_offset(url, cursor));
+                            }
+                        }
+                        KeyCode::Named(NamedKey::Backspace) => {
                             if cursor > 0 {
-                                url.remove(cursor - 1);
+                                url.remove(byte_offset(url, cursor - 1));
 
                                 self.cursor = Some(cursor - 1);
                             }
                         }
-                        key => {
-                            url.insert(cursor, key as char);
+                        KeyCode::Named(_) => (),
+                        KeyCode::Char(_) => {
+                            if let Some(char) = key.text() {
+                                url.insert(byte_offset(url, cursor), char);
 
-                            self.cursor = Some((cursor + 1).min(url.width()))
+                                self.cursor = Some(cursor + 1)
+                            }
                         }
                     }
 
@@ -95,6 +218,47 @@ impl Navigation {
         }
     }
 
+    /// Run an action bound in the keymap
+    fn action(&mut self, action: Action) -> NavigationAction {
+        match action {
+            Action::Back => NavigationAction::GoBack(),
+            Action::Forward => NavigationAction::GoForward(),
+            Action::Reload => NavigationAction::Refresh(),
+            Action::FocusUrl => {
+                self.cursor = Some(self.display_url().chars().count());
+
+                NavigationAction::Ignore
+            }
+            Action::Quit => NavigationAction::Quit(),
+            Action::ZoomIn => NavigationAction::ZoomIn(),
+            Action::ZoomOut => NavigationAction::ZoomOut(),
+            Action::ZoomReset => NavigationAction::ZoomReset(),
+            Action::Find => NavigationAction::Find(),
+            // Copy the URL when focused, the page selection otherwise
+            Action::Copy => match (self.cursor, &self.url) {
+                (Some(_), Some(url)) => NavigationAction::Copy(url.clone()),
+                _ => NavigationAction::CopySelection(),
+            },
+            Action::Paste => NavigationAction::ReadClipboard(),
+            Action::ColorFilter => NavigationAction::CycleColorFilter(),
+        }
+    }
+
+    /// Insert pasted text in the URL if focused, without its line breaks and control characters
+    pub fn paste(&mut self, text: &str) -> NavigationAction {
+        let (Some(cursor), Some(url)) = (self.cursor, &mut self.url) else {
+            return NavigationAction::Forward;
+        };
+        let text: String = text.chars().filter(|char| !char.is_control()).collect();
+        let cursor = cursor.min(url.chars().count());
+
+        url.insert_str(byte_offset(url, cursor), &text);
+
+        self.cursor = Some(cursor + text.chars().count());
+
+        NavigationAction::Ignore
+    }
+
     pub fn display_url(&self) -> &str {
         match &self.url {
             None => "about:blank",
@@ -103,7 +267,7 @@ impl Navigation {
     }
 
     pub fn url_size(&self) -> usize {
-        self.display_url().width()
+        self.widths.str(self.display_url())
     }
 
     pub fn mouse_up(&mut self, origin: Point) -> NavigationAction {
@@ -116,6 +280,8 @@ impl Navigation {
         }
     }
     pub fn mouse_down(&mut self, origin: Point) -> NavigationAction {
+        self.quit_prompt = None;
+
         if origin.y != 0 {
             self.cursor = None;
 
@@ -148,7 +314,7 @@ impl Navigation {
             (_, None) => true,
             (_, Some(current)) => current != url,
         } {
-            self.cursor = Some(url.len())
+            self.cursor = Some(url.chars().count())
         }
 
         self.url = Some(url.to_owned());
@@ -161,8 +327,7 @@ impl Navigation {
     }
 
     pub fn render_btn(&self, icon: &str, enabled: bool) -> [NavigationElement; 3] {
-        let background = Color::splat(255);
-        let foreground = Color::splat(0);
+        let (background, foreground, disabled) = self.colors();
 
         [
             NavigationElement {
@@ -173,11 +338,7 @@ impl Navigation {
             NavigationElement {
                 text: icon.to_owned(),
                 background,
-                foreground: if enabled {
-                    foreground
-                } else {
-                    Color::splat(200)
-                },
+                foreground: if enabled { foreground } else { disabled },
             },
             NavigationElement {
                 text: "]".to_owned(),
@@ -194,8 +355,17 @@ impl Navigation {
         } else {
             0
         };
-        let url: String = self.display_url().chars().take(space).collect();
-        let width = url.width();
+        let text = self.quit_prompt.as_deref().unwrap_or(self.display_url());
+        let mut width = 0;
+        let url: String = text
+            .graphemes(true)
+            .take_while(|grapheme| {
+                width += self.widths.grapheme(grapheme);
+
+                width <= space
+            })
+            .collect();
+        let width = self.widths.str(&url);
         let padded = format!(" {}{} ", url, " ".repeat(space - width));
         let mut elements = Vec::new();
         let mut point = Point::splat(0);
@@ -207,7 +377,7 @@ impl Navigation {
             self.render_btn(&padded, true),
         ] {
             for element in list {
-                let width = element.text.width() as i32;
+                let width = self.widths.str(&element.text) as i32;
 
                 elements.push((point, element));
 
@@ -218,3 +388,11 @@ impl Navigation {
         elements
     }
 }
+
+/// Byte offset of the character at `index` in a string, or its length
+fn byte_offset(string: &str, index: usize) -> usize {
+    string
+        .char_indices()
+        .nth(index)
+        .map_or(string.len(), |(offset, _)| offset)
+}
@@ -1,5 +1,8 @@
 mod four_bits;
+mod parallel;
 
+pub mod base64;
 pub mod log;
 
 pub use four_bits::*;
+pub use parallel::*;
@@ -0,0 +1,55 @@
+//! Standard base64 with padding, as used by OSC 52 clipboard sequences.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for index in 0..4 {
+            output.push(match index <= chunk.len() {
+                true => ALPHABET[(bits >> (18 - index * 6)) as usize & 0x3f] as char,
+                false => '=',
+            })
+        }
+    }
+
+    output
+}
+
+/// Decode base64 text, ignoring whitespace, returns `None` if it's invalid
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for byte in text.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            byte if byte.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+
+        bits = bits << 6 | value as u32;
+        count += 6;
+
+        if count >= 8 {
+            count -= 8;
+            output.push((bits >> count) as u8);
+        }
+    }
+
+    Some(output)
+}
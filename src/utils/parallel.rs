@@ -0,0 +1,134 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex, OnceLock,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// Whether the current thread is a worker of the pool
+    static WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Worker threads kept alive between calls to `parallel_map`
+struct Pool {
+    sender: Mutex<Sender<Job>>,
+    receiver: Arc<Mutex<Receiver<Job>>>,
+    workers: Mutex<usize>,
+}
+
+impl Pool {
+    fn get() -> &'static Pool {
+        static POOL: OnceLock<Pool> = OnceLock::new();
+
+        POOL.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+
+            Pool {
+                sender: Mutex::new(sender),
+                receiver: Arc::new(Mutex::new(receiver)),
+                workers: Mutex::new(0),
+            }
+        })
+    }
+
+    /// Spawn workers until there are at least `count` of them
+    fn reserve(&self, count: usize) {
+        let mut workers = self.workers.lock().unwrap();
+
+        while *workers < count {
+            let receiver = self.receiver.clone();
+
+            thread::Builder::new()
+                .name("carbonyl-worker".into())
+                .spawn(move || {
+                    WORKER.set(true);
+
+                    loop {
+                        // The lock is released before running the job
+                        let job = receiver.lock().unwrap().recv();
+
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => return,
+                        }
+                    }
+                })
+                .expect("failed to spawn worker thread");
+
+            *workers += 1
+        }
+    }
+
+    fn run(&self, job: Job) {
+        self.sender.lock().unwrap().send(job).unwrap()
+    }
+}
+
+/// Map `items` on worker threads, one item per worker, keeping their order.
+/// The first item is mapped on the calling thread.
+///
+/// Workers are spawned on demand and reused by later calls,
+/// so there are as many of them as the largest `items` minus one.
+///
+/// Must not be called from `map`: the pool only guarantees a number of workers,
+/// not that they're free, so a nested call could wait on workers busy waiting
+/// for it. Calls from several other threads at once are fine, they queue their
+/// items on the same workers.
+pub fn parallel_map<I, T, F>(items: Vec<I>, map: F) -> Vec<T>
+where
+    I: Send,
+    T: Send,
+    F: Fn(I) -> T + Sync,
+{
+    debug_assert!(
+        !WORKER.get(),
+        "parallel_map called from a worker thread, this could deadlock"
+    );
+
+    let mut items = items.into_iter();
+    let Some(first) = items.next() else {
+        return Vec::new();
+    };
+    let pool = Pool::get();
+    let map = &map;
+    let (sender, receiver) = mpsc::channel();
+    let count = items.len();
+
+    pool.reserve(count);
+
+    for (index, item) in items.enumerate() {
+        let sender = sender.clone();
+        let job: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+            let output = panic::catch_unwind(AssertUnwindSafe(|| map(item)));
+
+            sender.send((index, output)).unwrap()
+        });
+
+        // Safety: the job borrows `map`, `item` and the outputs, which outlive it
+        // as this function doesn't return before every job has sent its output,
+        // even if `map` panics. This relies on every job eventually running,
+        // which holds as long as no job waits on another, hence the restriction
+        // on nested calls above.
+        pool.run(unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + '_>, Job>(job) })
+    }
+
+    let first = panic::catch_unwind(AssertUnwindSafe(|| map(first)));
+    let mut rest = (0..count).map(|_| None).collect::<Vec<_>>();
+
+    for _ in 0..count {
+        let (index, output) = receiver.recv().unwrap();
+
+        rest[index] = Some(output)
+    }
+
+    std::iter::once(first)
+        .chain(rest.into_iter().flatten())
+        .map(|output| output.unwrap_or_else(|error| panic::resume_unwind(error)))
+        .collect()
+}
@@ -0,0 +1,26 @@
+use carbonyl::gfx::Color;
+
+#[test]
+fn average_mixes_in_linear_light() {
+    let black = Color::new(0, 0, 0);
+    let white = Color::new(255, 255, 255);
+    let gray = Color::average([black, white]);
+
+    // Averaging sRGB values would give 127
+    for value in [gray.r, gray.g, gray.b] {
+        assert!((186..=190).contains(&value), "{gray:?}");
+    }
+}
+
+#[test]
+fn average_of_equal_colors_is_identity() {
+    for color in [
+        Color::new(0, 0, 0),
+        Color::new(255, 255, 255),
+        Color::new(200, 100, 50),
+        Color::new(1, 128, 254),
+    ] {
+        assert_eq!(Color::average([color, color]), color);
+        assert_eq!(Color::average([color; 4]), color);
+    }
+}
@@ -0,0 +1,104 @@
+use carbonyl::{
+    gfx::{Color, Size},
+    output::{ColorFilter, Grid},
+};
+
+fn apply(filter: &str, color: (u8, u8, u8)) -> (u8, u8, u8) {
+    let filter: ColorFilter = filter.parse().unwrap();
+    let color = filter.apply(Color::new(color.0, color.1, color.2));
+
+    (color.r, color.g, color.b)
+}
+
+#[test]
+fn none_keeps_colors() {
+    assert_eq!(apply("none", (200, 100, 50)), (200, 100, 50));
+}
+
+#[test]
+fn invert_flips_lightness() {
+    for filter in ["invert", "invert-all"] {
+        assert_eq!(apply(filter, (255, 255, 255)), (0, 0, 0));
+        assert_eq!(apply(filter, (0, 0, 0)), (255, 255, 255));
+        // The hue is preserved
+        assert_eq!(apply(filter, (255, 0, 0)), (159, 0, 0));
+    }
+}
+
+#[test]
+fn invert_spares_images() {
+    let red = Color::new(255, 0, 0);
+    let white = Color::new(255, 255, 255);
+    let quadrant = (red, red, red, red);
+
+    let spared = ColorFilter::Invert { spare_images: true }.apply_cell(quadrant, Some(white));
+    let inverted = ColorFilter::Invert {
+        spare_images: false,
+    }
+    .apply_cell(quadrant, Some(white));
+
+    assert_eq!(spared, (quadrant, Some(white)));
+    assert_eq!(inverted.0.0, Color::new(159, 0, 0));
+    assert_eq!(inverted.1, Some(Color::new(0, 0, 0)));
+}
+
+#[test]
+fn grayscale_removes_chroma() {
+    assert_eq!(apply("grayscale", (255, 0, 0)), (136, 136, 136));
+    assert_eq!(apply("grayscale", (200, 100, 50)), (132, 132, 132));
+}
+
+#[test]
+fn sepia_tints_brown() {
+    assert_eq!(apply("sepia", (255, 255, 255)), (255, 255, 239));
+    assert_eq!(apply("sepia", (200, 100, 50)), (165, 147, 114));
+}
+
+#[test]
+fn night_reduces_blue() {
+    assert_eq!(apply("night", (255, 255, 255)), (243, 218, 179));
+    assert_eq!(apply("night", (0, 0, 0)), (0, 0, 0));
+}
+
+#[test]
+fn high_contrast_stretches_lightness() {
+    assert_eq!(apply("high-contrast", (200, 100, 50)), (235, 112, 47));
+    assert_eq!(apply("high-contrast", (255, 255, 255)), (255, 255, 255));
+}
+
+#[test]
+fn deuteranopia_merges_red_and_green() {
+    assert_eq!(apply("deuteranopia", (255, 0, 0)), (163, 144, 0));
+    assert_eq!(apply("deuteranopia", (0, 255, 0)), (239, 214, 58));
+}
+
+#[test]
+fn protanopia_darkens_red() {
+    assert_eq!(apply("protanopia", (255, 0, 0)), (109, 95, 0));
+    assert_eq!(apply("protanopia", (0, 255, 0)), (255, 229, 0));
+}
+
+#[test]
+fn navigation_row_is_not_filtered() {
+    let white = Color::new(255, 255, 255).pack();
+    let mut grid = Grid::new(Size::new(2, 2));
+
+    grid.rows_mut(0..2).0.fill([white; 4]);
+
+    let glyphs = grid.changes(1, ColorFilter::Invert { spare_images: true });
+    let backgrounds = glyphs
+        .iter()
+        .flatten()
+        .map(|glyph| (glyph.cursor.y, glyph.background))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        backgrounds,
+        [
+            (0, Color::new(255, 255, 255)),
+            (0, Color::new(255, 255, 255)),
+            (1, Color::new(0, 0, 0)),
+            (1, Color::new(0, 0, 0)),
+        ]
+    );
+}
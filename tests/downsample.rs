@@ -0,0 +1,45 @@
+use carbonyl::{
+    gfx::Color,
+    output::{average_rows, average_rows_scalar},
+};
+
+fn pixels(count: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+
+    (0..count * 4)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn kernels_match_color_average() {
+    // Odd lengths exercise the scalar remainder of the SIMD version
+    for count in [0, 1, 7, 8, 9, 64, 1283] {
+        let upper = pixels(count, 0x1234_5678);
+        let mut lower = pixels(count, 0x8765_4321);
+
+        // Make some runs of pixels identical in both rows
+        lower[..count * 2].copy_from_slice(&upper[..count * 2]);
+
+        let expected = upper
+            .chunks_exact(4)
+            .zip(lower.chunks_exact(4))
+            .map(|(a, b)| {
+                Color::average([Color::new(a[2], a[1], a[0]), Color::new(b[2], b[1], b[0])])
+            })
+            .collect::<Vec<_>>();
+        let mut scalar = vec![Color::black(); count];
+        let mut dispatched = vec![Color::black(); count];
+
+        average_rows_scalar(&upper, &lower, &mut scalar);
+        average_rows(&upper, &lower, &mut dispatched);
+
+        assert_eq!(scalar, expected, "scalar, {count} pixels");
+        assert_eq!(dispatched, expected, "dispatched, {count} pixels");
+    }
+}
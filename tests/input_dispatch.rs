@@ -0,0 +1,249 @@
+use std::{
+    ffi::CStr,
+    io,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+    time::Duration,
+};
+
+use carbonyl::{
+    browser::{BrowserDelegate, InputDispatch, SharedScale},
+    gfx::{Color, Point, Size},
+    input::{Event, Key, KeyModifiers, Parser},
+    output::{Painter, RenderThread, Renderer},
+};
+use libc::{c_char, c_int, c_uint, c_void};
+
+/// Calls received by the mock browser
+#[derive(Debug, PartialEq)]
+enum Call {
+    KeyPress(u8),
+    MouseMove(c_uint, c_uint),
+}
+
+static CALLS: Mutex<Vec<Call>> = Mutex::new(Vec::new());
+
+extern "C" fn noop() {}
+extern "C" fn go_to(_: *const c_char) {}
+extern "C" fn scroll(_: c_int) {}
+extern "C" fn mouse(_: c_uint, _: c_uint) {}
+extern "C" fn key_press(char: c_char) {
+    CALLS.lock().unwrap().push(Call::KeyPress(char as u8))
+}
+extern "C" fn mouse_move(x: c_uint, y: c_uint) {
+    CALLS.lock().unwrap().push(Call::MouseMove(x, y))
+}
+/// Run tasks right away, on the rendering thread
+extern "C" fn post_task(run: extern "C" fn(*mut c_void), data: *mut c_void) {
+    run(data)
+}
+
+const DELEGATE: BrowserDelegate = BrowserDelegate {
+    shutdown: noop,
+    refresh: noop,
+    go_to,
+    go_back: noop,
+    go_forward: noop,
+    scroll,
+    key_press,
+    mouse_up: mouse,
+    mouse_down: mouse,
+    mouse_move,
+    post_task,
+    resize: None,
+    key_event: None,
+    paste: None,
+    mouse_event: None,
+    horizontal_scroll: None,
+    focus: None,
+    copy: None,
+    zoom: None,
+    find: None,
+    color_scheme: None,
+};
+
+/// Key event received by the mock browser: action, key, code, text and modifiers
+type KeyEvent = (c_int, String, String, u32, c_uint);
+
+static KEY_EVENTS: Mutex<Vec<KeyEvent>> = Mutex::new(Vec::new());
+
+extern "C" fn key_event(
+    action: c_int,
+    key: *const c_char,
+    code: *const c_char,
+    text: u32,
+    modifiers: c_uint,
+) {
+    let string = |value| {
+        unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
+
+    KEY_EVENTS
+        .lock()
+        .unwrap()
+        .push((action, string(key), string(code), text, modifiers))
+}
+extern "C" fn ignore_key(_: c_char) {
+    panic!("key sent through the legacy callback")
+}
+
+/// Scales swapped while dispatching, mixing their dimensions would be noticed
+const SCALES: [Size<f32>; 2] = [Size::new(2.0, 4.0), Size::new(7.0, 11.0)];
+
+fn key(char: u8) -> Event {
+    Event::KeyPress {
+        key: Key::char(char as char, KeyModifiers::default()),
+    }
+}
+
+#[test]
+fn dispatch_under_load() {
+    const BATCHES: usize = 2000;
+
+    let mut thread = RenderThread::with_renderer(|| {
+        Renderer::with_painter(Painter::with_output(Box::new(io::sink())))
+    });
+    let scale = Arc::new(SharedScale::new(SCALES[0]));
+    let dispatch = InputDispatch::new(thread.sender(), scale.clone(), DELEGATE);
+
+    thread.enable();
+    thread.render(|renderer| renderer.set_size(Size::new(80, 24), Size::new(2.0, 4.0)));
+
+    let (done, finished) = mpsc::channel();
+
+    // Input thread: batches of key presses and mouse moves
+    let input = thread::spawn(move || {
+        for batch in 0..BATCHES {
+            dispatch.dispatch(vec![
+                key(b'a' + (batch % 26) as u8),
+                // Cell centers
+                Event::MouseMove {
+                    col: (batch % 80) as f32 + 0.5,
+                    row: (1 + batch % 23) as f32 + 0.5,
+                    button: None,
+                    modifiers: KeyModifiers::default(),
+                },
+            ]);
+        }
+
+        dispatch
+    });
+    // Resize thread: swap the scale back and forth
+    let resize = {
+        let scale = scale.clone();
+
+        thread::spawn(move || {
+            for index in 0..BATCHES {
+                scale.store(SCALES[index % 2]);
+                thread::yield_now();
+            }
+        })
+    };
+    // Browser thread: draw text concurrently
+    let draw = {
+        let sender = thread.sender();
+
+        thread::spawn(move || {
+            for index in 0..BATCHES / 10 {
+                sender.render(move |renderer| {
+                    renderer.clear_text();
+                    renderer.draw_text(
+                        &format!("line {index}"),
+                        Point::new(0, 7),
+                        Size::new(0, 0),
+                        Color::new(255, 255, 255),
+                    );
+                });
+            }
+        })
+    };
+
+    let dispatch = input.join().unwrap();
+
+    resize.join().unwrap();
+    draw.join().unwrap();
+
+    // Runs after every batch dispatched before it
+    thread.render(move |_| done.send(()).unwrap());
+    finished
+        .recv_timeout(Duration::from_secs(30))
+        .expect("input was not handled in time");
+
+    let calls = std::mem::take(&mut *CALLS.lock().unwrap());
+
+    assert_eq!(calls.len(), BATCHES * 2);
+
+    for (batch, calls) in calls.chunks(2).enumerate() {
+        assert_eq!(calls[0], Call::KeyPress(b'a' + (batch % 26) as u8));
+
+        let Call::MouseMove(x, y) = calls[1] else {
+            panic!("expected a mouse move, got {:?}", calls[1])
+        };
+        let (col, row) = ((batch % 80) as f32, (1 + batch % 23) as f32);
+
+        assert!(
+            SCALES.iter().any(|scale| {
+                x == (scale.width * (col + 0.5)).floor() as c_uint
+                    && y == (scale.height * (row - 0.5)).floor() as c_uint
+            }),
+            "batch {batch}: ({x}, {y}) doesn't match a single scale"
+        );
+    }
+
+    drop(dispatch);
+    thread.stop().unwrap().join().unwrap();
+}
+
+#[test]
+fn key_events() {
+    let mut thread = RenderThread::with_renderer(|| {
+        Renderer::with_painter(Painter::with_output(Box::new(io::sink())))
+    });
+    let scale = Arc::new(SharedScale::new(SCALES[0]));
+    let delegate = BrowserDelegate {
+        key_press: ignore_key,
+        key_event: Some(key_event),
+        ..DELEGATE
+    };
+    let dispatch = InputDispatch::new(thread.sender(), scale, delegate);
+    let (done, finished) = mpsc::channel();
+
+    thread.enable();
+    thread.render(|renderer| renderer.set_size(Size::new(80, 24), Size::new(2.0, 4.0)));
+    dispatch.dispatch(
+        Parser::new().parse("\x1b[1;5A\x1b[97;2;65u\x1b[97;2:3u\u{e9}\x1b[98;213u".as_bytes()),
+    );
+    thread.render(move |_| done.send(()).unwrap());
+    finished
+        .recv_timeout(Duration::from_secs(30))
+        .expect("input was not handled in time");
+
+    let events = std::mem::take(&mut *KEY_EVENTS.lock().unwrap());
+    let event = |action, key: &str, code: &str, text: char, modifiers| {
+        (
+            action,
+            key.to_owned(),
+            code.to_owned(),
+            text as u32,
+            modifiers,
+        )
+    };
+
+    assert_eq!(
+        events,
+        [
+            event(0, "ArrowUp", "ArrowUp", '\0', 4),
+            event(0, "A", "KeyA", 'A', 1),
+            event(2, "A", "KeyA", 'A', 1),
+            event(0, "\u{e9}", "", '\u{e9}', 0),
+            // Hyper, caps lock and num lock are left out
+            event(0, "b", "KeyB", '\0', 4),
+        ]
+    );
+
+    drop(dispatch);
+    thread.stop().unwrap().join().unwrap();
+}
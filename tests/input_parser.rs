@@ -0,0 +1,511 @@
+use carbonyl::{
+    gfx::{Color, Size},
+    input::{
+        Event, Key, KeyAction, KeyCode, KeyModifiers, MouseButton, NamedKey, Parser, TerminalEvent,
+    },
+    ui::navigation::ColorScheme,
+};
+
+/// Parse input and describe each key press as (code, shift, alt, control)
+fn keys(input: &[u8]) -> Vec<(KeyCode, bool, bool, bool)> {
+    Parser::new()
+        .parse(input)
+        .into_iter()
+        .map(|event| match event {
+            Event::KeyPress { key } => (
+                key.code,
+                key.modifiers.shift,
+                key.modifiers.alt,
+                key.modifiers.control,
+            ),
+            event => panic!("expected a key press, got {event:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn key_sequences() {
+    use NamedKey::*;
+
+    let named = |key| (KeyCode::Named(key), false, false, false);
+
+    assert_eq!(
+        keys(b"\x1b[A\x1b[B\x1b[C\x1b[D\x1b[H\x1b[F\x1b[E"),
+        [Up, Down, Right, Left, Home, End, Begin].map(named)
+    );
+    assert_eq!(
+        keys(b"\x1b[1~\x1b[2~\x1b[3~\x1b[4~\x1b[5~\x1b[6~\x1b[7~\x1b[8~"),
+        [Home, Insert, Delete, End, PageUp, PageDown, Home, End].map(named)
+    );
+    assert_eq!(
+        keys(b"\x1bOP\x1bOQ\x1bOR\x1bOS\x1b[15~\x1b[17~\x1b[18~\x1b[19~\x1b[20~\x1b[21~\x1b[23~\x1b[24~"),
+        (1..=12).map(|n| named(Function(n))).collect::<Vec<_>>()
+    );
+    assert_eq!(keys(b"\x1bOA\x1bOH"), [Up, Home].map(named));
+    // Modifiers
+    assert_eq!(
+        keys(b"\x1b[1;5A\x1b[1;2P\x1b[3;3~\x1b[15;6~\x1bO5D"),
+        [
+            (KeyCode::Named(Up), false, false, true),
+            (KeyCode::Named(Function(1)), true, false, false),
+            (KeyCode::Named(Delete), false, true, false),
+            (KeyCode::Named(Function(5)), true, false, true),
+            (KeyCode::Named(Left), false, false, true),
+        ]
+    );
+    // Shift+Tab and modifyOtherKeys
+    assert_eq!(
+        keys(b"\x1b[Z\x1b[27;5;13~"),
+        [
+            (KeyCode::Named(Tab), true, false, false),
+            (KeyCode::Named(Enter), false, false, true)
+        ]
+    );
+    // Sequences split across reads
+    let mut parser = Parser::new();
+
+    assert!(parser.parse(b"\x1b[1").is_empty());
+    assert!(parser.parse(b"5;5").is_empty());
+    assert!(matches!(
+        parser.parse(b"~a").as_slice(),
+        [Event::KeyPress { key: f5 }, Event::KeyPress { key: a }]
+            if f5.code == KeyCode::Named(Function(5))
+                && f5.modifiers.control
+                && a.code == KeyCode::Char('a')
+    ));
+}
+
+#[test]
+fn kitty_keys() {
+    let events = Parser::new()
+        .parse(b"\x1b[105;5u\x1b[9u\x1b[97;2:2;65u\x1b[1;3:3A\x1b[27u\x1b[57399u\x1b[233u");
+    let keys: Vec<_> = events
+        .iter()
+        .map(|event| match event {
+            Event::KeyPress { key } => (
+                key.code,
+                key.modifiers.control,
+                key.modifiers.shift,
+                key.action,
+            ),
+            event => panic!("expected a key press, got {event:?}"),
+        })
+        .collect();
+
+    assert_eq!(
+        keys,
+        [
+            // Ctrl+I and Tab
+            (KeyCode::Char('i'), true, false, KeyAction::Press),
+            (
+                KeyCode::Named(NamedKey::Tab),
+                false,
+                false,
+                KeyAction::Press
+            ),
+            (KeyCode::Char('A'), false, true, KeyAction::Repeat),
+            (
+                KeyCode::Named(NamedKey::Up),
+                false,
+                false,
+                KeyAction::Release
+            ),
+            (
+                KeyCode::Named(NamedKey::Escape),
+                false,
+                false,
+                KeyAction::Press
+            ),
+            (KeyCode::Char('0'), false, false, KeyAction::Press),
+            (KeyCode::Char('é'), false, false, KeyAction::Press),
+        ]
+    );
+
+    // Ctrl+C is a key like any other, sent to the page
+    let events = Parser::new().parse(b"\x1b[?3u\x1b[99;5u\x1b[99;5:3u\x03");
+
+    assert!(matches!(
+        events.as_slice(),
+        [
+            Event::Terminal(TerminalEvent::KeyboardFlags(3)),
+            Event::KeyPress { key: press },
+            Event::KeyPress { key: release },
+            Event::KeyPress { key: legacy },
+        ] if press.code == KeyCode::Char('c')
+            && press.modifiers.control
+            && release.action == KeyAction::Release
+            && *legacy == *press
+    ));
+}
+
+#[test]
+fn unicode_keys() {
+    let mut parser = Parser::new();
+    // Characters split across reads
+    let mut events = parser.parse("aé漢".as_bytes());
+
+    events.extend(parser.parse(&"🦀".as_bytes()[..2]));
+    events.extend(parser.parse(&"🦀".as_bytes()[2..]));
+    events.extend(parser.parse(b"\x01\x09\x0d\x7f\x1c"));
+
+    let keys: Vec<_> = events
+        .into_iter()
+        .map(|event| match event {
+            Event::KeyPress { key } => key,
+            event => panic!("expected a key press, got {event:?}"),
+        })
+        .collect();
+    let control = KeyModifiers {
+        control: true,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        keys,
+        [
+            Key::char('a', KeyModifiers::default()),
+            Key::char('é', KeyModifiers::default()),
+            Key::char('漢', KeyModifiers::default()),
+            Key::char('🦀', KeyModifiers::default()),
+            Key::char('a', control),
+            Key::named(NamedKey::Tab, KeyModifiers::default()),
+            Key::named(NamedKey::Enter, KeyModifiers::default()),
+            Key::named(NamedKey::Backspace, KeyModifiers::default()),
+            Key::char('\\', control),
+        ]
+    );
+
+    // DOM values and bytes for the legacy callback
+    let describe = |key: &Key| {
+        (
+            key.dom_key().into_owned(),
+            key.dom_code(),
+            key.text(),
+            key.legacy_byte(),
+        )
+    };
+
+    assert_eq!(describe(&keys[1]), ("é".to_owned(), "", Some('é'), None));
+    assert_eq!(
+        describe(&keys[4]),
+        ("a".to_owned(), "KeyA", None, Some(0x01))
+    );
+    assert_eq!(
+        describe(&Key::char('?', KeyModifiers::default())),
+        ("?".to_owned(), "Slash", Some('?'), Some(b'?'))
+    );
+    assert_eq!(
+        describe(&Key::named(NamedKey::Left, KeyModifiers::default())),
+        ("ArrowLeft".to_owned(), "ArrowLeft", None, Some(0x14))
+    );
+    assert_eq!(
+        describe(&Key::named(NamedKey::Function(12), control)),
+        ("F12".to_owned(), "F12", None, None)
+    );
+}
+
+#[test]
+fn bracketed_paste() {
+    let mut parser = Parser::new();
+    let mut events = parser.parse(b"a\x1b[200~https://example.com\r\nline\x03\x1b[2");
+
+    events.extend(parser.parse(b"01~b"));
+
+    assert!(matches!(
+        events.as_slice(),
+        [
+            Event::KeyPress { key: a },
+            Event::Paste(text),
+            Event::KeyPress { key: b },
+        ] if a.code == KeyCode::Char('a')
+            && text == "https://example.com\nline\x03"
+            && b.code == KeyCode::Char('b')
+    ));
+}
+
+/// Describe mouse events as (kind, row, col, button, control)
+fn mouse(events: Vec<Event>) -> Vec<(&'static str, f32, f32, Option<MouseButton>, bool)> {
+    events
+        .into_iter()
+        .map(|event| match event {
+            Event::MouseDown {
+                row,
+                col,
+                button,
+                modifiers,
+            } => ("down", row, col, Some(button), modifiers.control),
+            Event::MouseUp {
+                row,
+                col,
+                button,
+                modifiers,
+            } => ("up", row, col, Some(button), modifiers.control),
+            Event::MouseMove {
+                row,
+                col,
+                button,
+                modifiers,
+            } => ("move", row, col, button, modifiers.control),
+            Event::Scroll { delta } => ("scroll", delta as f32, 0.0, None, false),
+            Event::HorizontalScroll { delta } => ("horizontal", delta as f32, 0.0, None, false),
+            event => panic!("expected a mouse event, got {event:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn mouse_buttons() {
+    use MouseButton::*;
+
+    let events = Parser::new()
+        .parse(b"\x1b[<2;5;3M\x1b[<2;5;3m\x1b[<17;1;1M\x1b[<32;2;2M\x1b[<35;9;9M\x1b[<128;1;1M");
+
+    assert_eq!(
+        mouse(events),
+        [
+            ("down", 2.5, 4.5, Some(Right), false),
+            ("up", 2.5, 4.5, Some(Right), false),
+            // Ctrl+Middle click
+            ("down", 0.5, 0.5, Some(Middle), true),
+            // Drag with the left button, then a move without button
+            ("move", 1.5, 1.5, Some(Left), false),
+            ("move", 8.5, 8.5, None, false),
+            ("down", 0.5, 0.5, Some(Back), false),
+        ]
+    );
+}
+
+#[test]
+fn mouse_wheel_and_pixels() {
+    // Up, down, left, right, then Shift+up and Shift+down
+    let events = Parser::new()
+        .parse(b"\x1b[<64;1;1M\x1b[<65;1;1M\x1b[<66;1;1M\x1b[<67;1;1M\x1b[<68;1;1M\x1b[<69;1;1M");
+
+    assert_eq!(
+        mouse(events),
+        [
+            ("scroll", 1.0, 0.0, None, false),
+            ("scroll", -1.0, 0.0, None, false),
+            ("horizontal", 1.0, 0.0, None, false),
+            ("horizontal", -1.0, 0.0, None, false),
+            ("horizontal", 1.0, 0.0, None, false),
+            ("horizontal", -1.0, 0.0, None, false),
+        ]
+    );
+
+    // SGR-Pixels support, reported as reset, then positions with 10x20 pixel cells
+    let mut parser = Parser::new();
+
+    assert!(matches!(
+        parser.parse(b"\x1b[?1016;2$y").as_slice(),
+        [Event::Terminal(TerminalEvent::Mode {
+            mode: 1016,
+            state: 2
+        })]
+    ));
+
+    parser.set_cell_pixels(Some(Size::new(10.0, 20.0)));
+
+    assert_eq!(
+        mouse(parser.parse(b"\x1b[<0;26;51M")),
+        [("down", 2.5, 2.5, Some(MouseButton::Left), false)]
+    );
+}
+
+#[test]
+fn focus_events() {
+    let events = Parser::new().parse(b"\x1b[O\x1b[Ia");
+
+    assert!(matches!(
+        events.as_slice(),
+        [
+            Event::Focus { focused: false },
+            Event::Focus { focused: true },
+            Event::KeyPress { .. }
+        ]
+    ));
+}
+
+#[test]
+fn cursor_position_reports() {
+    let events = Parser::new().parse(b"\x1b[2;3R\x1b[?1;2R\x1b[1;5R");
+
+    assert_eq!(
+        events[..2],
+        [
+            Event::Terminal(TerminalEvent::CursorPosition { row: 2, col: 3 }),
+            Event::Terminal(TerminalEvent::CursorPosition { row: 1, col: 2 }),
+        ]
+    );
+
+    // Without the private marker, a report on the first row is F3 with modifiers
+    assert_eq!(
+        keys(b"\x1b[1;5R"),
+        [(KeyCode::Named(NamedKey::Function(3)), false, false, true)]
+    );
+    assert_eq!(events.len(), 3);
+}
+
+#[test]
+fn clipboard_replies() {
+    let clipboard = |input: &[u8]| match Parser::new().parse(input).as_slice() {
+        [Event::Terminal(TerminalEvent::Clipboard(text))] => Some(text.clone()),
+        _ => None,
+    };
+
+    // Terminated by BEL or ST
+    assert_eq!(
+        clipboard(b"\x1b]52;c;aHR0cHM6Ly9leGFtcGxlLmNvbQ==\x07").as_deref(),
+        Some("https://example.com")
+    );
+    assert_eq!(
+        clipboard(b"\x1b]52;c;w6k=\x1b\\").as_deref(),
+        Some("\u{e9}")
+    );
+    assert_eq!(clipboard(b"\x1b]52;c;\x07").as_deref(), Some(""));
+    // Not base64
+    assert_eq!(clipboard(b"\x1b]52;c;?\x07"), None);
+    // Other replies are ignored, and don't swallow the following keys
+    assert!(matches!(
+        Parser::new().parse(b"\x1b]0;title\x07a").as_slice(),
+        [Event::KeyPress { .. }]
+    ));
+}
+
+#[test]
+fn terminal_colors() {
+    let events = Parser::new().parse(
+        concat!(
+            "\x1b]10;rgb:ffff/ffff/ffff\x1b\\",
+            "\x1b]11;rgb:1e1e/1e1e/2e2e\x07",
+            "\x1b]4;3;rgb:cd/0/80\x1b\\",
+            "\x1b]4;12;#5c5cff\x07",
+            "\x1b]11;rgb:ff/ff\x07",
+        )
+        .as_bytes(),
+    );
+
+    assert_eq!(
+        events,
+        [
+            Event::Terminal(TerminalEvent::ForegroundColor(Color::new(255, 255, 255))),
+            Event::Terminal(TerminalEvent::BackgroundColor(Color::new(30, 30, 46))),
+            Event::Terminal(TerminalEvent::PaletteColor {
+                index: 3,
+                color: Color::new(205, 0, 128),
+            }),
+            Event::Terminal(TerminalEvent::PaletteColor {
+                index: 12,
+                color: Color::new(92, 92, 255),
+            }),
+        ]
+    );
+
+    let scheme = |r, g, b| ColorScheme::from_background(Color::new(r, g, b));
+
+    assert_eq!(scheme(30, 30, 46), ColorScheme::Dark);
+    assert_eq!(scheme(0, 43, 54), ColorScheme::Dark);
+    assert_eq!(scheme(255, 255, 255), ColorScheme::Light);
+    assert_eq!(scheme(253, 246, 227), ColorScheme::Light);
+}
+
+#[test]
+fn terminal_color_long_channels() {
+    // Channels longer than 4 hex digits are invalid, and must not overflow
+    let events = Parser::new().parse(
+        concat!(
+            "\x1b]11;rgb:ffffffff/0/0\x07",
+            "\x1b]10;rgb:0/ffffffffffffffff/0\x1b\\",
+            "\x1b]11;rgb:0/0/ff\x07",
+        )
+        .as_bytes(),
+    );
+
+    assert_eq!(
+        events,
+        [Event::Terminal(TerminalEvent::BackgroundColor(Color::new(
+            0, 0, 255
+        )))]
+    );
+}
+
+#[test]
+fn escape_and_alt() {
+    let alt = |code| (code, false, true, false);
+
+    // Keys sent after an escape are pressed with Alt
+    assert_eq!(
+        keys("\x1bx\x1b\u{e9}\x1b\x7f\x1b\x01".as_bytes()),
+        [
+            alt(KeyCode::Char('x')),
+            alt(KeyCode::Char('\u{e9}')),
+            alt(KeyCode::Named(NamedKey::Backspace)),
+            (KeyCode::Char('a'), false, true, true),
+        ]
+    );
+
+    // A lone escape waits for the next read, or for the timeout
+    let mut parser = Parser::new();
+
+    assert!(parser.parse(b"\x1b").is_empty());
+    assert!(parser.ambiguous());
+    assert!(matches!(
+        parser.timeout().as_slice(),
+        [Event::KeyPress { key }] if key.code == KeyCode::Named(NamedKey::Escape)
+    ));
+    assert!(!parser.ambiguous());
+    assert!(parser.timeout().is_empty());
+
+    // Introducers not followed by a sequence are pressed with Alt
+    for (input, char) in [
+        ("\x1b[", '['),
+        ("\x1bO", 'O'),
+        ("\x1bP", 'P'),
+        ("\x1b]", ']'),
+    ] {
+        assert!(parser.parse(input.as_bytes()).is_empty());
+        assert!(parser.ambiguous());
+        assert_eq!(
+            parser.timeout(),
+            [Event::KeyPress {
+                key: Key::char(
+                    char,
+                    KeyModifiers {
+                        alt: true,
+                        ..Default::default()
+                    }
+                )
+            }]
+        );
+    }
+
+    // Sequences started are not ambiguous anymore
+    assert!(parser.parse(b"\x1b[1;").is_empty());
+    assert!(!parser.ambiguous());
+    assert!(parser.timeout().is_empty());
+}
+
+#[test]
+fn sequences_split_across_reads() {
+    let input = concat!(
+        "a\x1b[1;5A\x1b[97;2:3u\u{e9}\x1bx\x1bOP\x1b[<0;12;5M",
+        "\x1b[200~pasted\r\ntext\x1b[201~\x1b]11;rgb:0/0/0\x1b\\",
+        "\x1bP1+r544e=787465726d\x1b\\\x1b[I\x1b\x1b",
+    )
+    .as_bytes();
+    let expected = Parser::new().parse(input);
+
+    // The last escape waits for the next read
+    assert_eq!(expected.len(), 12);
+
+    // Byte by byte, then in chunks of every size
+    for size in 1..input.len() {
+        let mut parser = Parser::new();
+        let events = input
+            .chunks(size)
+            .flat_map(|chunk| parser.parse(chunk))
+            .collect::<Vec<_>>();
+
+        assert_eq!(events, expected, "chunks of {size} bytes");
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    ffi::CStr,
+    io::{self, Write},
+    sync::{Arc, Mutex, mpsc},
+    time::{Duration, Instant},
+};
+
+use carbonyl::{
+    browser::{BrowserDelegate, InputDispatch, SharedScale},
+    gfx::{Color, Size},
+    input::{
+        Event, Key, KeyAction, KeyModifiers, MouseButton, NamedKey, Parser, Recorder,
+        TerminalEvent, replay,
+    },
+    output::{Painter, RenderThread, Renderer},
+};
+use libc::{c_char, c_int, c_uint, c_void};
+
+/// Recording shared with the test
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Calls received by the mock browser
+#[derive(Debug, PartialEq)]
+enum Call {
+    Key(c_int, String),
+    Paste(String),
+    Mouse(c_int, c_uint, c_uint, c_int),
+    Shutdown,
+}
+
+static CALLS: Mutex<Vec<Call>> = Mutex::new(Vec::new());
+
+fn string(value: *const c_char) -> String {
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+extern "C" fn shutdown() {
+    CALLS.lock().unwrap().push(Call::Shutdown)
+}
+extern "C" fn noop() {}
+extern "C" fn go_to(_: *const c_char) {}
+extern "C" fn scroll(_: c_int) {}
+extern "C" fn key_press(_: c_char) {}
+extern "C" fn mouse(_: c_uint, _: c_uint) {}
+extern "C" fn post_task(run: extern "C" fn(*mut c_void), data: *mut c_void) {
+    run(data)
+}
+extern "C" fn key_event(action: c_int, key: *const c_char, _: *const c_char, _: u32, _: c_uint) {
+    CALLS.lock().unwrap().push(Call::Key(action, string(key)))
+}
+extern "C" fn paste(text: *const c_char) {
+    CALLS.lock().unwrap().push(Call::Paste(string(text)))
+}
+extern "C" fn mouse_event(kind: c_int, x: c_uint, y: c_uint, button: c_int, _: c_uint) {
+    CALLS.lock().unwrap().push(Call::Mouse(kind, x, y, button))
+}
+
+const DELEGATE: BrowserDelegate = BrowserDelegate {
+    shutdown,
+    refresh: noop,
+    go_to,
+    go_back: noop,
+    go_forward: noop,
+    scroll,
+    key_press,
+    mouse_up: mouse,
+    mouse_down: mouse,
+    mouse_move: mouse,
+    post_task,
+    resize: None,
+    key_event: Some(key_event),
+    paste: Some(paste),
+    mouse_event: Some(mouse_event),
+    horizontal_scroll: None,
+    focus: None,
+    copy: None,
+    zoom: None,
+    find: None,
+    color_scheme: None,
+};
+
+#[test]
+fn recording_round_trip() {
+    let control = KeyModifiers {
+        control: true,
+        ..Default::default()
+    };
+    let batches = vec![
+        vec![
+            Event::Terminal(TerminalEvent::Name("xterm(390) 100%".to_owned())),
+            Event::Terminal(TerminalEvent::TrueColorSupported),
+            Event::Terminal(TerminalEvent::CellPixels(Size::new(10, 20))),
+            Event::Terminal(TerminalEvent::Mode {
+                mode: 1016,
+                state: 2,
+            }),
+            Event::Terminal(TerminalEvent::CursorPosition { row: 2, col: 3 }),
+        ],
+        vec![
+            Event::KeyPress {
+                key: Key::char('\u{e9}', control),
+            },
+            Event::KeyPress {
+                key: Key {
+                    action: KeyAction::Release,
+                    ..Key::named(NamedKey::Function(12), KeyModifiers::parse(0b1000_0010))
+                },
+            },
+            Event::Paste("two words\nand an emoji 👋🏽".to_owned()),
+            Event::Paste(String::new()),
+        ],
+        vec![
+            Event::MouseDown {
+                row: 3.5,
+                col: 12.25,
+                button: MouseButton::Back,
+                modifiers: control,
+            },
+            Event::MouseMove {
+                row: 4.5,
+                col: 0.1,
+                button: None,
+                modifiers: KeyModifiers::default(),
+            },
+            Event::Scroll { delta: -3 },
+            Event::HorizontalScroll { delta: 2 },
+            Event::Focus { focused: false },
+            Event::Terminal(TerminalEvent::Clipboard(" % ".to_owned())),
+            Event::Terminal(TerminalEvent::BackgroundColor(Color::new(0, 43, 54))),
+            Event::Terminal(TerminalEvent::PaletteColor {
+                index: 15,
+                color: Color::new(255, 255, 255),
+            }),
+        ],
+    ];
+    let output = Output::default();
+    let mut recorder = Recorder::with_output(Box::new(output.clone())).unwrap();
+
+    for batch in &batches {
+        recorder.record(batch).unwrap();
+        // Separate batches by their timestamp
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    let recording = output.0.lock().unwrap().clone();
+    let mut replayed = Vec::new();
+
+    replay(recording.as_slice(), 0.0, |events| replayed.push(events)).unwrap();
+    assert_eq!(replayed, batches);
+
+    // Replaying with delays
+    let recording = b"# carbonyl input 1\n0 scroll 1\n\n100 scroll -1\n";
+    let start = Instant::now();
+
+    replay(recording.as_slice(), 5.0, |_| ()).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    // Errors report their line
+    let error = replay(b"0 key press 0 nope".as_slice(), 0.0, |_| ()).unwrap_err();
+
+    assert_eq!(error.to_string(), "line 1: invalid key: nope");
+
+    // Quitting goes through the keymap, recordings have no exit event
+    let error = replay(b"0 exit".as_slice(), 0.0, |_| ()).unwrap_err();
+
+    assert_eq!(error.to_string(), "line 1: invalid event: exit");
+}
+
+#[test]
+fn replay_to_browser() {
+    let mut thread = RenderThread::with_renderer(|| {
+        Renderer::with_painter(Painter::with_output(Box::new(io::sink())))
+    });
+    let scale = Arc::new(SharedScale::new(Size::new(2.0, 4.0)));
+    let dispatch = InputDispatch::new(thread.sender(), scale, DELEGATE);
+    let (done, finished) = mpsc::channel();
+    let recording = concat!(
+        "# carbonyl input 1\n",
+        "0 key press 1 U+0041\n",
+        "0 key release 1 U+0041\n",
+        "15 paste hello%20world\n",
+        "30 mouse-down 10.5 3.5 left 0\n",
+        "30 mouse-up 10.5 3.5 left 0\n",
+        "40 key press 4 U+0071\n",
+    );
+
+    thread.enable();
+    thread.render(|renderer| renderer.set_size(Size::new(80, 24), Size::new(2.0, 4.0)));
+
+    replay(recording.as_bytes(), 0.0, |events| {
+        dispatch.dispatch(events)
+    })
+    .unwrap();
+
+    thread.render(move |_| done.send(()).unwrap());
+    finished
+        .recv_timeout(Duration::from_secs(30))
+        .expect("input was not handled in time");
+
+    assert_eq!(
+        std::mem::take(&mut *CALLS.lock().unwrap()),
+        [
+            Call::Key(0, "A".to_owned()),
+            Call::Key(2, "A".to_owned()),
+            Call::Paste("hello world".to_owned()),
+            Call::Mouse(0, 21, 10, 0),
+            Call::Mouse(1, 21, 10, 0),
+            // Ctrl+Q quits
+            Call::Shutdown,
+        ]
+    );
+
+    // Recordings match what the parser reports
+    let output = Output::default();
+    let mut recorder = Recorder::with_output(Box::new(output.clone())).unwrap();
+
+    recorder
+        .record(&Parser::new().parse(b"\x1b[97;2u\x1b[200~hi\x1b[201~"))
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(output.0.lock().unwrap().clone()).unwrap(),
+        "# carbonyl input 1\n0 key press 1 U+0041\n0 paste hi\n"
+    );
+
+    drop(dispatch);
+    thread.stop().unwrap().join().unwrap();
+}
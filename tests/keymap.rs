@@ -0,0 +1,127 @@
+use carbonyl::{
+    gfx::Size,
+    input::{Event, Key, KeyAction, KeyModifiers, NamedKey, Parser},
+    ui::{
+        keymap::{Action, Keymap},
+        navigation::{Navigation, NavigationAction},
+    },
+};
+
+fn key(input: &[u8]) -> Key {
+    match Parser::new().parse(input).as_slice() {
+        [Event::KeyPress { key }] => key.clone(),
+        events => panic!("expected a key press, got {events:?}"),
+    }
+}
+
+#[test]
+fn default_bindings() {
+    let keymap = Keymap::default();
+
+    assert!(keymap.errors().is_empty());
+    // Legacy control bytes, kitty sequences and function keys
+    assert_eq!(keymap.action(&key(b"\x0c")), Some(Action::FocusUrl));
+    assert_eq!(keymap.action(&key(b"\x1b[108;5u")), Some(Action::FocusUrl));
+    assert_eq!(keymap.action(&key(b"\x1b[15~")), Some(Action::Reload));
+    assert_eq!(keymap.action(&key(b"\x1c")), Some(Action::ColorFilter));
+    // Letters are reported uppercase with Shift
+    assert_eq!(keymap.action(&key(b"\x1b[99;6u")), Some(Action::Copy));
+    // Shifted symbols match as typed
+    assert_eq!(keymap.action(&key(b"\x1b[43;6u")), Some(Action::ZoomIn));
+    // Zoom from kitty sequences, and from legacy terminals
+    assert_eq!(keymap.action(&key(b"\x1b[61;5u")), Some(Action::ZoomIn));
+    assert_eq!(keymap.action(&key(b"\x1b[45;5u")), Some(Action::ZoomOut));
+    assert_eq!(keymap.action(&key(b"\x1b[48;5u")), Some(Action::ZoomReset));
+    assert_eq!(keymap.action(&key(b"\x1f")), Some(Action::ZoomOut));
+    assert_eq!(keymap.action(&key(b"\x1b=")), Some(Action::ZoomIn));
+    assert_eq!(keymap.action(&key(b"\x1b-")), Some(Action::ZoomOut));
+    assert_eq!(keymap.action(&key(b"\x1b0")), Some(Action::ZoomReset));
+    assert_eq!(keymap.action(&key(b"=")), None);
+    assert_eq!(keymap.action(&key(b"l")), None);
+    assert_eq!(keymap.action(&key(b"\x1b[1;2C")), None);
+}
+
+#[test]
+fn overrides_and_conflicts() {
+    let keymap = Keymap::parse(
+        "# Comment\n\
+         reload = ctrl+shift+r, F9\n\
+         find = none\n\
+         zoom-in = ctrl++\n\
+         back = ctrl+l\n\
+         search = ctrl+k\n\
+         quit = ctrl+nope+q\n",
+    );
+    let ctrl = |char| {
+        Key::char(
+            char,
+            KeyModifiers {
+                control: true,
+                ..Default::default()
+            },
+        )
+    };
+
+    assert_eq!(keymap.action(&ctrl('R')), Some(Action::Reload));
+    assert_eq!(keymap.action(&ctrl('r')), None);
+    assert_eq!(
+        keymap.action(&Key::named(NamedKey::Function(9), Default::default())),
+        Some(Action::Reload)
+    );
+    assert_eq!(keymap.action(&ctrl('f')), None);
+    assert_eq!(keymap.action(&ctrl('=')), None);
+    assert_eq!(keymap.action(&ctrl('+')), Some(Action::ZoomIn));
+    // The first binding wins, invalid lines keep the defaults
+    assert_eq!(keymap.action(&ctrl('l')), Some(Action::FocusUrl));
+    assert_eq!(keymap.action(&ctrl('q')), Some(Action::Quit));
+    assert_eq!(
+        keymap.errors(),
+        [
+            "line 6: unknown action: search",
+            "line 7: unknown modifier in \"ctrl+nope+q\": nope",
+            "ctrl+l is bound to both focus-url and back, using focus-url",
+        ]
+    );
+}
+
+#[test]
+fn quit_confirmation() {
+    let mut nav = Navigation::new();
+    let text = |nav: &Navigation| {
+        nav.render(Size::new(80, 24))
+            .into_iter()
+            .map(|(_, element)| element.text)
+            .collect::<String>()
+    };
+    let quit = key(b"\x11");
+
+    nav.push("https://example.com", false, false);
+
+    // Ctrl+C goes to the page
+    assert!(matches!(
+        nav.keypress(&key(b"\x03")),
+        NavigationAction::Forward
+    ));
+    assert!(matches!(nav.keypress(&quit), NavigationAction::Quit()));
+
+    nav.set_confirm_quit(true);
+
+    // Asked to press again, holding the shortcut or pressing another key doesn't quit
+    assert!(matches!(nav.keypress(&quit), NavigationAction::Ignore));
+    assert!(text(&nav).contains("Press ctrl+q again to quit"));
+
+    let repeat = Key {
+        action: KeyAction::Repeat,
+        ..quit.clone()
+    };
+
+    assert!(matches!(nav.keypress(&repeat), NavigationAction::Ignore));
+    assert!(matches!(
+        nav.keypress(&key(b"a")),
+        NavigationAction::Forward
+    ));
+    assert!(text(&nav).contains("https://example.com"));
+    assert!(matches!(nav.keypress(&quit), NavigationAction::Ignore));
+    assert!(matches!(nav.keypress(&repeat), NavigationAction::Ignore));
+    assert!(matches!(nav.keypress(&quit), NavigationAction::Quit()));
+}
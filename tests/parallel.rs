@@ -0,0 +1,70 @@
+use std::{
+    collections::HashSet,
+    panic,
+    sync::Mutex,
+    thread::{self, ThreadId},
+};
+
+use carbonyl::utils::parallel_map;
+
+#[test]
+fn keeps_order_and_borrows() {
+    let data = (0..1000).collect::<Vec<u32>>();
+    let bands = data.chunks(100).collect::<Vec<_>>();
+    let sums = parallel_map(bands, |band| band.iter().sum::<u32>());
+
+    assert_eq!(
+        sums,
+        (0..10)
+            .map(|band| (band * 100..band * 100 + 100).sum::<u32>())
+            .collect::<Vec<_>>()
+    );
+    assert!(parallel_map(Vec::<u32>::new(), |item| item).is_empty());
+}
+
+#[test]
+fn reuses_worker_threads() {
+    let threads = Mutex::new(HashSet::<ThreadId>::new());
+    let caller = thread::current().id();
+
+    for _ in 0..20 {
+        parallel_map((0..4).collect(), |_: u32| {
+            threads.lock().unwrap().insert(thread::current().id())
+        });
+    }
+
+    let threads = threads.into_inner().unwrap();
+
+    // Other tests might run concurrently and grow the pool
+    assert!(threads.contains(&caller));
+    assert!(threads.len() < 20);
+}
+
+#[test]
+fn propagates_panics() {
+    let result = panic::catch_unwind(|| {
+        parallel_map((0..4).collect(), |item: u32| {
+            assert_ne!(item, 2, "worker panic");
+            item
+        })
+    });
+
+    assert!(result.is_err());
+    // Workers survive the panic
+    assert_eq!(
+        parallel_map((0..4).collect(), |item: u32| item * 2),
+        [0, 2, 4, 6]
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn rejects_nested_calls() {
+    let result = panic::catch_unwind(|| {
+        parallel_map((0..2).collect(), |item: u32| {
+            parallel_map(vec![item], |item| item)
+        })
+    });
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,314 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use carbonyl::{
+    gfx::{Color, Point, Rect, Size},
+    input::{Key, KeyModifiers},
+    output::{Painter, Renderer},
+    ui::navigation::NavigationAction,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Terminal output shared with the test
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Terminal taking a while to process each frame
+#[derive(Clone, Default)]
+struct SlowOutput(Output);
+
+impl Write for SlowOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        thread::sleep(Duration::from_millis(50));
+
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Deterministic BGRA bitmap, shifted by `seed` between frames
+fn bitmap(size: Size, seed: u32) -> Vec<u8> {
+    let mut state = 0x9e37_79b9_u32 ^ seed;
+
+    (0..size.width * size.height)
+        .flat_map(|index| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            // Flat bands spanning whole rows with some noise, so that colors carry over
+            // from one row to the next as well as change within rows.
+            let flat = ((index / size.width / 48 + seed) as u8).wrapping_mul(37);
+            let noise = state.to_le_bytes();
+
+            match state % 64 {
+                0 => [noise[0], noise[1], noise[2], 255],
+                _ => [flat, flat.wrapping_mul(3), flat.wrapping_add(90), 255],
+            }
+        })
+        .collect()
+}
+
+fn renderer(output: &Output, threads: usize, true_color: bool, cells: Size) -> Renderer {
+    let mut painter = Painter::with_output(Box::new(output.clone()));
+
+    painter.set_true_color(true_color);
+
+    let mut renderer = Renderer::with_painter(painter);
+
+    renderer.set_threads(threads);
+    renderer.set_size(cells, Size::new(2.0, 4.0));
+    renderer
+}
+
+fn draw_frame(renderer: &mut Renderer, cells: Size, frame: u32) {
+    let pixels = Size::new(cells.width * 2, cells.height * 4);
+    // Alternate between full and partial damage
+    let damage = match frame % 2 {
+        0 => Rect::new(0, 0, pixels.width, pixels.height),
+        _ => Rect::new(37, 91, pixels.width / 2, pixels.height / 3),
+    };
+
+    renderer.draw_background(&bitmap(pixels, frame), pixels, damage);
+    renderer.clear_text();
+
+    for line in 0..cells.height as i32 / 3 {
+        renderer.draw_text(
+            "Hello, world! 你好，世界 👋🏽 carbonyl",
+            Point::new(((line * 7 + frame as i32 * 3) % 80) * 2, line * 12 + 3),
+            Size::new(0, 0),
+            Color::new(200, (line * 20) as u8, 40),
+        );
+    }
+}
+
+#[test]
+fn parallel_output_matches_sequential() {
+    let cells = Size::new(240, 90);
+
+    for true_color in [false, true] {
+        let sequential_output = Output::default();
+        let parallel_output = Output::default();
+        let mut sequential = renderer(&sequential_output, 1, true_color, cells);
+        let mut parallel = renderer(&parallel_output, 7, true_color, cells);
+
+        for frame in 0..6 {
+            draw_frame(&mut sequential, cells, frame);
+            draw_frame(&mut parallel, cells, frame);
+            assert!(sequential.render().unwrap());
+            assert!(parallel.render().unwrap());
+            sequential.flush();
+            parallel.flush();
+
+            let expected = sequential_output.take();
+            let actual = parallel_output.take();
+
+            assert!(!expected.is_empty());
+            assert!(
+                expected == actual,
+                "frame {frame} differs (true color: {true_color})"
+            );
+        }
+    }
+}
+
+#[test]
+fn busy_terminal_skips_frames() {
+    let output = SlowOutput::default();
+    let cells = Size::new(80, 24);
+    let mut renderer = Renderer::with_painter(Painter::with_output(Box::new(output.clone())));
+
+    renderer.set_size(cells, Size::new(2.0, 4.0));
+
+    // Labels are letters that don't appear in escape sequences
+    for frame in ['a', 'b', 'c', 'd'] {
+        renderer.clear_text();
+        renderer.draw_text(
+            &format!("frame {frame}"),
+            Point::new(0, 7),
+            Size::new(0, 0),
+            Color::new(255, 255, 255),
+        );
+        renderer.render().unwrap();
+    }
+
+    assert!(renderer.skipped_frames() > 0);
+
+    // Changes of skipped frames are painted with the next one
+    renderer.flush();
+    assert!(renderer.render().unwrap());
+    renderer.flush();
+
+    let written = String::from_utf8(output.0.take()).unwrap();
+    // Frames start by hiding the cursor
+    let last = written.rsplit("\x1b[?25l").next().unwrap();
+
+    assert!(last.contains('d'), "last frame: {last:?}");
+}
+
+#[test]
+fn clipboard_sequences() {
+    let output = Output::default();
+    let mut renderer = renderer(&output, 1, false, Size::new(80, 24));
+    let shortcut = |char| {
+        Key::char(
+            char,
+            KeyModifiers {
+                control: true,
+                shift: true,
+                ..Default::default()
+            },
+        )
+    };
+
+    renderer.push_nav("https://example.com", false, false);
+
+    // The page copies its own selection
+    assert!(matches!(
+        renderer.keypress(&shortcut('C')).unwrap(),
+        NavigationAction::CopySelection()
+    ));
+
+    // Copy the URL once focused, and ask for the clipboard
+    renderer.mouse_down(Point::new(12, 0)).unwrap();
+
+    for char in ['C', 'V'] {
+        assert!(matches!(
+            renderer.keypress(&shortcut(char)).unwrap(),
+            NavigationAction::Ignore
+        ));
+    }
+
+    renderer.set_clipboard("\u{e9}").unwrap();
+    assert!(renderer.render().unwrap());
+    renderer.flush();
+
+    let written = String::from_utf8(output.take()).unwrap();
+
+    assert!(written.starts_with(concat!(
+        "\x1b]52;c;aHR0cHM6Ly9leGFtcGxlLmNvbQ==\x07",
+        "\x1b]52;c;?\x07",
+        "\x1b]52;c;w6k=\x07",
+    )));
+}
+
+#[test]
+fn title_goes_through_the_writer() {
+    let output = Output::default();
+    let mut renderer = renderer(&output, 1, false, Size::new(20, 5));
+
+    renderer.set_title("Example").unwrap();
+    renderer.set_title("Carbonyl").unwrap();
+
+    // Sent along with the next frame, not from the render thread
+    assert!(output.take().is_empty());
+    assert!(renderer.render().unwrap());
+    renderer.flush();
+
+    let written = String::from_utf8(output.take()).unwrap();
+
+    assert!(written.starts_with(concat!(
+        "\x1b]0;Example\x07\x1b]1;Example\x07\x1b]2;Example\x07",
+        "\x1b]0;Carbonyl\x07\x1b]1;Carbonyl\x07\x1b]2;Carbonyl\x07",
+    )));
+}
+
+/// Replay painted output on the terminal, `cells` being the size of the page
+/// below the navigation bar, and return its rows.
+///
+/// Only cursor moves are interpreted, other escape sequences are skipped.
+/// Background block elements are shown as spaces, and wide graphemes
+/// leave the other cells they cover empty.
+fn screen(output: &[u8], cells: Size) -> Vec<String> {
+    let output = String::from_utf8(output.to_vec()).unwrap();
+    let mut grid = vec![vec![String::from(" "); cells.width as usize]; cells.height as usize + 1];
+    let mut cursor = (0, 0);
+    let mut rest = output.as_str();
+
+    while let Some(grapheme) = rest.graphemes(true).next() {
+        if let Some(sequence) = rest.strip_prefix("\x1b[") {
+            let end = sequence.find(|c: char| c.is_ascii_alphabetic()).unwrap();
+
+            if sequence[end..].starts_with('H') {
+                let (row, col) = sequence[..end].split_once(';').unwrap();
+
+                cursor = (
+                    row.parse::<usize>().unwrap() - 1,
+                    col.parse::<usize>().unwrap() - 1,
+                );
+            }
+
+            rest = &sequence[end + 1..];
+        } else if let Some(sequence) = rest.strip_prefix("\x1b]") {
+            rest = &sequence[sequence.find('\x07').unwrap() + 1..];
+        } else {
+            let (row, col) = cursor;
+
+            grid[row][col] = match grapheme.chars().next() {
+                Some('\u{2580}'..='\u{259f}') => " ".to_owned(),
+                _ => grapheme.to_owned(),
+            };
+
+            for cell in 1..grapheme.width() {
+                grid[row][col + cell].clear()
+            }
+
+            cursor.1 += grapheme.width();
+            rest = &rest[grapheme.len()..];
+        }
+    }
+
+    grid.into_iter().map(|row| row.concat()).collect()
+}
+
+#[test]
+fn text_runs_clipping_and_overlaps() {
+    let output = Output::default();
+    let cells = Size::new(20, 5);
+    let mut renderer = renderer(&output, 1, true, cells);
+    let white = Color::new(255, 255, 255);
+
+    // Cut at the right edge of the terminal, with an ellipsis in the last cell
+    renderer.draw_text("Hello, world!", Point::new(28, 6), Size::new(0, 0), white);
+    // Cut to the run's own width
+    renderer.draw_text("carbonyl", Point::new(0, 10), Size::new(8, 0), white);
+    // Half of a wide grapheme overwritten by a later run on the same baseline
+    renderer.draw_text("你好", Point::new(0, 14), Size::new(0, 0), white);
+    renderer.draw_text("a", Point::new(2, 14), Size::new(0, 0), white);
+    // A closer baseline wins over a later run
+    renderer.draw_text("y", Point::new(10, 13), Size::new(0, 0), white);
+    renderer.draw_text("x", Point::new(10, 14), Size::new(0, 0), white);
+
+    assert!(renderer.render().unwrap());
+    renderer.flush();
+
+    let rows = screen(&output.take(), cells);
+
+    assert_eq!(rows[1], format!("{}Hello…", " ".repeat(14)));
+    assert_eq!(rows[2], format!("car…{}", " ".repeat(16)));
+    assert_eq!(rows[3], format!(" a好 y{}", " ".repeat(14)));
+}
@@ -0,0 +1,82 @@
+use std::{
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use carbonyl::input::watch_resize;
+
+static PREVIOUS_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+type InfoHandler = extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void);
+
+extern "C" fn previous(_: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    if !info.is_null() {
+        PREVIOUS_CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Currently installed SIGWINCH handler
+fn installed() -> libc::sighandler_t {
+    unsafe {
+        let mut action = MaybeUninit::<libc::sigaction>::zeroed().assume_init();
+
+        libc::sigaction(libc::SIGWINCH, std::ptr::null(), &mut action);
+        action.sa_sigaction
+    }
+}
+
+/// Wait until `done` returns true, panicking after a generous deadline
+fn wait_for(what: &str, done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn resize_burst_is_debounced_and_chained() {
+    let handler = previous as InfoHandler as libc::sighandler_t;
+
+    unsafe {
+        let mut action = MaybeUninit::<libc::sigaction>::zeroed().assume_init();
+
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        assert_eq!(
+            libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || watch_resize(true, || sender.send(()).unwrap()));
+
+    wait_for("the watcher to install its handler", || {
+        installed() != handler
+    });
+
+    unsafe {
+        libc::raise(libc::SIGWINCH);
+        libc::raise(libc::SIGWINCH);
+    }
+
+    // The previous handler runs for every signal, the callback once per burst
+    wait_for("the previous handler", || {
+        PREVIOUS_CALLS.load(Ordering::SeqCst) == 2
+    });
+    receiver
+        .recv_timeout(Duration::from_secs(10))
+        .expect("callback wasn't called");
+    assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(PREVIOUS_CALLS.load(Ordering::SeqCst), 2);
+}
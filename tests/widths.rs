@@ -0,0 +1,76 @@
+use carbonyl::{
+    gfx::{Point, Size},
+    output::{GraphemeKind, Widths},
+    ui::navigation::Navigation,
+};
+
+#[test]
+fn probed_widths() {
+    // A terminal clustering graphemes measures samples like unicode-width
+    let clustered = Widths::from_probe(&[
+        (GraphemeKind::Emoji, 2),
+        (GraphemeKind::Modifier, 2),
+        (GraphemeKind::Presentation, 2),
+        (GraphemeKind::Sequence, 2),
+        (GraphemeKind::Ambiguous, 1),
+    ]);
+
+    assert!(clustered.is_empty());
+    assert_eq!(clustered.str("👩‍💻 ①"), 4);
+
+    // Another one adds up characters and uses a CJK locale
+    let widths = Widths::from_probe(&[
+        (GraphemeKind::Emoji, 2),
+        (GraphemeKind::Modifier, 4),
+        (GraphemeKind::Presentation, 1),
+        (GraphemeKind::Sequence, 4),
+        (GraphemeKind::Ambiguous, 2),
+    ]);
+
+    assert_eq!(
+        widths.to_string(),
+        "modifier=chars,presentation=chars,sequence=chars,ambiguous=cjk"
+    );
+    assert_eq!(widths.to_string().parse(), Ok(widths.clone()));
+
+    for (grapheme, width) in [
+        ("a", 1),
+        ("😀", 2),
+        ("👍🏽", 4),
+        ("❤️", 1),
+        ("👨‍👩‍👧", 6),
+        ("①", 2),
+        ("…", 2),
+        ("漢", 2),
+    ] {
+        assert_eq!(widths.grapheme(grapheme), width, "{grapheme}");
+    }
+
+    assert_eq!(widths.str("a👩‍💻①"), 7);
+    assert!("sequence=wide".parse::<Widths>().is_err());
+}
+
+#[test]
+fn navigation_widths() {
+    let mut nav = Navigation::new();
+    let url = "https://example.com/👩‍💻";
+
+    nav.push(url, false, false);
+    nav.mouse_down(Point::new(11, 0));
+    nav.push(&format!("{url}/"), false, false);
+
+    assert_eq!(nav.cursor(), Some(Point::new(34, 0)));
+
+    nav.set_widths("sequence=chars".parse().unwrap());
+
+    assert_eq!(nav.cursor(), Some(Point::new(36, 0)));
+
+    // The URL is cut before graphemes overflowing the bar, which keeps its width
+    let elements = nav.render(Size::new(34, 24));
+    let [.., (_, url), (end, _)] = elements.as_slice() else {
+        panic!("missing URL")
+    };
+
+    assert_eq!(url.text, " https://example.com/  ");
+    assert_eq!(end.x, 33);
+}